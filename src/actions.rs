@@ -1,22 +1,372 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::game_state::{CELL_COLOR_COUNT, PENCIL_COLOR_COUNT};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_ref() {
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        }
+    }
+
+    /// Row and column offsets of a single step in this direction
+    pub fn offset(&self) -> (i8, i8) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_ref() {
+            "topleft" => Some(Corner::TopLeft),
+            "topright" => Some(Corner::TopRight),
+            "bottomleft" => Some(Corner::BottomLeft),
+            "bottomright" => Some(Corner::BottomRight),
+            _ => None,
+        }
+    }
 
-#[derive(Clone, Debug)]
+    fn name(&self) -> &'static str {
+        match self {
+            Corner::TopLeft => "topLeft",
+            Corner::TopRight => "topRight",
+            Corner::BottomLeft => "bottomLeft",
+            Corner::BottomRight => "bottomRight",
+        }
+    }
+
+    /// Row and column of this corner in a grid of side `size`
+    pub fn cell(&self, size: usize) -> (usize, usize) {
+        let last = size - 1;
+        match self {
+            Corner::TopLeft => (0, 0),
+            Corner::TopRight => (0, last),
+            Corner::BottomLeft => (last, 0),
+            Corner::BottomRight => (last, last),
+        }
+    }
+}
+
+/// Which mistakes are shown on the board
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorMode {
+    Off,
+    /// Digits conflicting with a peer holding the same digit
+    RuleConflicts,
+    /// Digits different from the solution
+    VsSolution,
+    Both,
+}
+
+impl ErrorMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_ref() {
+            "off" => Some(ErrorMode::Off),
+            "ruleconflicts" => Some(ErrorMode::RuleConflicts),
+            "vssolution" => Some(ErrorMode::VsSolution),
+            "both" => Some(ErrorMode::Both),
+            _ => None,
+        }
+    }
+
+    /// Name of the mode in the config notation read by `from_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorMode::Off => "off",
+            ErrorMode::RuleConflicts => "ruleConflicts",
+            ErrorMode::VsSolution => "vsSolution",
+            ErrorMode::Both => "both",
+        }
+    }
+
+    pub(crate) fn shows_conflicts(&self) -> bool {
+        matches!(self, ErrorMode::RuleConflicts | ErrorMode::Both)
+    }
+
+    pub(crate) fn shows_mismatches(&self) -> bool {
+        matches!(self, ErrorMode::VsSolution | ErrorMode::Both)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub enum Action {
     MoveRow(i8, bool),
     MoveCol(i8, bool),
+    /// Moves to the next empty cell in a direction, skipping filled cells
+    MoveSkipFilled(Direction),
+    /// Moves to the next empty cell in reading order, wrapping around the grid
+    NextEmpty,
+    /// Moves to the previous empty cell in reading order, wrapping around the grid
+    PrevEmpty,
+    /// Remembers the focused cell under a name
+    SetMark(char),
+    /// Moves to the cell remembered under a name
+    JumpToMark(char),
+    /// Moves to the cell at a row and column
+    FocusCell(u8, u8),
+    /// Moves to the top-left cell of the next box in reading order, wrapping around the grid
+    NextBox,
+    /// Moves to the top-left cell of the previous box in reading order, wrapping around the grid
+    PrevBox,
+    /// Moves to the center cell of the grid
+    FocusCenter,
+    /// Moves to a corner cell of the grid
+    FocusCorner(Corner),
     WriteCell(u8),
     SetCandidate(u8),
     RemoveCandidate(u8),
     ToggleCandidate(u8),
     ClearCandidates,
+    /// Clears the candidates of every cell of the board
+    ClearAllCandidates,
     ClearCell,
+    /// Writes the digit of every cell of the focused box that has a single candidate marked
+    FillBoxSingles,
+    /// Marks the only legal digit of every empty cell that has a single one as its candidate
+    MarkForcedCandidates,
+    /// Replaces the candidates of every empty cell by the digits that can legally go there
+    FillCandidates,
+    /// Fills every empty cell when the board can be finished with singles alone, never guessing
+    AutoComplete,
+    /// Writes the solution digit in the focused cell if it's empty, marking it as a hint
+    Hint,
+    /// Clears every digit entered by the player that differs from the solution
+    ClearMistakes,
+    /// Marks the focused cell as uncertain, or unmarks it
+    ToggleFlag,
     CycleColor,
-    // Unsure if useful when cycling between 3 choices is already fast
-    // TODO SetColor(PRIMARY/SECONDARY/CLEAR)?
+    /// Sets the background color of the focused cell, 0 clearing it
+    SetColor(u8),
     ClearAllColors,
     HighlightCurrentDigit,
     HighlightDigit(u8),
     ClearHighlight,
+    /// Sets the color of the candidates marked from now on
+    SetPencilColor(u8),
+    ToggleAutoNotes,
+    ToggleInputMode,
+    SetErrorMode(ErrorMode),
+    /// Hides the mistakes shown on the board, or shows them again with the previous error mode
+    ToggleErrors,
+    /// Resets transient UI state (highlighted digit, given overwrite waiting for confirmation,
+    /// rejected action flag) without touching the board or the focused cell
+    ClearAll,
+    Undo,
+    Redo,
+    /// Generates a new puzzle with the same settings as the current one
+    NewGame,
+    /// Clears every digit and candidate entered by the player
+    Restart,
+    /// Clears the whole board, givens included
+    ClearBoard,
+}
+
+/// Kind of an action, without its arguments
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    MoveRow,
+    MoveCol,
+    MoveSkipFilled,
+    NextEmpty,
+    PrevEmpty,
+    SetMark,
+    JumpToMark,
+    FocusCell,
+    NextBox,
+    PrevBox,
+    FocusCenter,
+    FocusCorner,
+    WriteCell,
+    SetCandidate,
+    RemoveCandidate,
+    ToggleCandidate,
+    ClearCandidates,
+    ClearAllCandidates,
+    ClearCell,
+    FillBoxSingles,
+    MarkForcedCandidates,
+    FillCandidates,
+    AutoComplete,
+    Hint,
+    ClearMistakes,
+    ToggleFlag,
+    CycleColor,
+    SetColor,
+    ClearAllColors,
+    HighlightCurrentDigit,
+    HighlightDigit,
+    ClearHighlight,
+    SetPencilColor,
+    ToggleAutoNotes,
+    ToggleInputMode,
+    SetErrorMode,
+    ToggleErrors,
+    ClearAll,
+    Undo,
+    Redo,
+    NewGame,
+    Restart,
+    ClearBoard,
+}
+
+impl ActionKind {
+    pub const ALL: [ActionKind; 43] = [
+        ActionKind::MoveRow,
+        ActionKind::MoveCol,
+        ActionKind::MoveSkipFilled,
+        ActionKind::NextEmpty,
+        ActionKind::PrevEmpty,
+        ActionKind::SetMark,
+        ActionKind::JumpToMark,
+        ActionKind::FocusCell,
+        ActionKind::NextBox,
+        ActionKind::PrevBox,
+        ActionKind::FocusCenter,
+        ActionKind::FocusCorner,
+        ActionKind::WriteCell,
+        ActionKind::SetCandidate,
+        ActionKind::RemoveCandidate,
+        ActionKind::ToggleCandidate,
+        ActionKind::ClearCandidates,
+        ActionKind::ClearAllCandidates,
+        ActionKind::ClearCell,
+        ActionKind::FillBoxSingles,
+        ActionKind::MarkForcedCandidates,
+        ActionKind::FillCandidates,
+        ActionKind::AutoComplete,
+        ActionKind::Hint,
+        ActionKind::ClearMistakes,
+        ActionKind::ToggleFlag,
+        ActionKind::CycleColor,
+        ActionKind::SetColor,
+        ActionKind::ClearAllColors,
+        ActionKind::HighlightCurrentDigit,
+        ActionKind::HighlightDigit,
+        ActionKind::ClearHighlight,
+        ActionKind::SetPencilColor,
+        ActionKind::ToggleAutoNotes,
+        ActionKind::ToggleInputMode,
+        ActionKind::SetErrorMode,
+        ActionKind::ToggleErrors,
+        ActionKind::ClearAll,
+        ActionKind::Undo,
+        ActionKind::Redo,
+        ActionKind::NewGame,
+        ActionKind::Restart,
+        ActionKind::ClearBoard,
+    ];
+}
+
+impl Action {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Action::MoveRow(..) => ActionKind::MoveRow,
+            Action::MoveCol(..) => ActionKind::MoveCol,
+            Action::MoveSkipFilled(_) => ActionKind::MoveSkipFilled,
+            Action::NextEmpty => ActionKind::NextEmpty,
+            Action::PrevEmpty => ActionKind::PrevEmpty,
+            Action::SetMark(_) => ActionKind::SetMark,
+            Action::JumpToMark(_) => ActionKind::JumpToMark,
+            Action::FocusCell(..) => ActionKind::FocusCell,
+            Action::NextBox => ActionKind::NextBox,
+            Action::PrevBox => ActionKind::PrevBox,
+            Action::FocusCenter => ActionKind::FocusCenter,
+            Action::FocusCorner(_) => ActionKind::FocusCorner,
+            Action::WriteCell(_) => ActionKind::WriteCell,
+            Action::SetCandidate(_) => ActionKind::SetCandidate,
+            Action::RemoveCandidate(_) => ActionKind::RemoveCandidate,
+            Action::ToggleCandidate(_) => ActionKind::ToggleCandidate,
+            Action::ClearCandidates => ActionKind::ClearCandidates,
+            Action::ClearAllCandidates => ActionKind::ClearAllCandidates,
+            Action::ClearCell => ActionKind::ClearCell,
+            Action::FillBoxSingles => ActionKind::FillBoxSingles,
+            Action::MarkForcedCandidates => ActionKind::MarkForcedCandidates,
+            Action::FillCandidates => ActionKind::FillCandidates,
+            Action::AutoComplete => ActionKind::AutoComplete,
+            Action::Hint => ActionKind::Hint,
+            Action::ClearMistakes => ActionKind::ClearMistakes,
+            Action::ToggleFlag => ActionKind::ToggleFlag,
+            Action::CycleColor => ActionKind::CycleColor,
+            Action::SetColor(_) => ActionKind::SetColor,
+            Action::ClearAllColors => ActionKind::ClearAllColors,
+            Action::HighlightCurrentDigit => ActionKind::HighlightCurrentDigit,
+            Action::HighlightDigit(_) => ActionKind::HighlightDigit,
+            Action::ClearHighlight => ActionKind::ClearHighlight,
+            Action::SetPencilColor(_) => ActionKind::SetPencilColor,
+            Action::ToggleAutoNotes => ActionKind::ToggleAutoNotes,
+            Action::ToggleInputMode => ActionKind::ToggleInputMode,
+            Action::SetErrorMode(_) => ActionKind::SetErrorMode,
+            Action::ToggleErrors => ActionKind::ToggleErrors,
+            Action::ClearAll => ActionKind::ClearAll,
+            Action::Undo => ActionKind::Undo,
+            Action::Redo => ActionKind::Redo,
+            Action::NewGame => ActionKind::NewGame,
+            Action::Restart => ActionKind::Restart,
+            Action::ClearBoard => ActionKind::ClearBoard,
+        }
+    }
+
+    /// Returns the digit the action acts upon, if any
+    pub fn digit(&self) -> Option<u8> {
+        match self {
+            Action::WriteCell(n)
+            | Action::SetCandidate(n)
+            | Action::RemoveCandidate(n)
+            | Action::ToggleCandidate(n)
+            | Action::HighlightDigit(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Mark names are a single letter or digit
+fn parse_mark_name(arg: &str) -> Option<char> {
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Some(c),
+        _ => None,
+    }
 }
 
 fn parse_action_string(value: &str) -> Option<(String, Vec<String>)> {
@@ -28,15 +378,8 @@ fn parse_action_string(value: &str) -> Option<(String, Vec<String>)> {
     // Split string into 2 parts, action name and arguments
     // Check that both parts exist (opening bracket is found)
     let mut iter = value.split('(').map(String::from);
-    let name = match iter.next() {
-        Some(s) => s,
-        None => return None,
-    };
-
-    let mut args = match iter.next() {
-        Some(s) => s,
-        None => return None,
-    };
+    let name = iter.next()?;
+    let mut args = iter.next()?;
 
     // Should only have 1 opening bracket, meaning next iter item should be None
     if iter.next().is_some() {
@@ -86,9 +429,31 @@ impl TryFrom<String> for Action {
                 "cyclecolor" => Ok(Action::CycleColor),
                 "clearallcolors" => Ok(Action::ClearAllColors),
                 "clearcandidates" => Ok(Action::ClearCandidates),
+                "clearallcandidates" => Ok(Action::ClearAllCandidates),
                 "clearcell" => Ok(Action::ClearCell),
+                "fillboxsingles" => Ok(Action::FillBoxSingles),
+                "markforced" => Ok(Action::MarkForcedCandidates),
+                "fillcandidates" => Ok(Action::FillCandidates),
+                "autocomplete" => Ok(Action::AutoComplete),
+                "hint" => Ok(Action::Hint),
+                "clearmistakes" => Ok(Action::ClearMistakes),
+                "focuscenter" => Ok(Action::FocusCenter),
+                "nextbox" => Ok(Action::NextBox),
+                "prevbox" => Ok(Action::PrevBox),
+                "nextempty" => Ok(Action::NextEmpty),
+                "prevempty" => Ok(Action::PrevEmpty),
+                "toggleflag" => Ok(Action::ToggleFlag),
                 "highlightcurrentdigit" => Ok(Action::HighlightCurrentDigit),
                 "clearhighlight" => Ok(Action::ClearHighlight),
+                "toggleautonotes" => Ok(Action::ToggleAutoNotes),
+                "toggleinputmode" => Ok(Action::ToggleInputMode),
+                "toggleerrors" => Ok(Action::ToggleErrors),
+                "clearall" => Ok(Action::ClearAll),
+                "undo" => Ok(Action::Undo),
+                "redo" => Ok(Action::Redo),
+                "newgame" => Ok(Action::NewGame),
+                "restart" => Ok(Action::Restart),
+                "clearboard" => Ok(Action::ClearBoard),
                 _ => Err(ActionParsingError(value.clone())),
             };
         }
@@ -151,6 +516,21 @@ impl TryFrom<String> for Action {
                         None
                     }
                 }
+                "setpencilcolor" => args[0]
+                    .parse()
+                    .ok()
+                    .filter(|color| (1..=PENCIL_COLOR_COUNT).contains(color))
+                    .map(Action::SetPencilColor),
+                "setcolor" => args[0]
+                    .parse()
+                    .ok()
+                    .filter(|&color| color <= CELL_COLOR_COUNT)
+                    .map(Action::SetColor),
+                "moveskip" => Direction::from_name(&args[0]).map(Action::MoveSkipFilled),
+                "setmark" => parse_mark_name(&args[0]).map(Action::SetMark),
+                "jumpmark" => parse_mark_name(&args[0]).map(Action::JumpToMark),
+                "focuscorner" => Corner::from_name(&args[0]).map(Action::FocusCorner),
+                "seterrormode" => ErrorMode::from_name(&args[0]).map(Action::SetErrorMode),
                 _ => None,
             }
             .ok_or(ActionParsingError(value.clone()));
@@ -173,6 +553,15 @@ impl TryFrom<String> for Action {
                         None
                     }
                 }
+                "focuscell" => {
+                    // Coordinates are checked against the grid size when applied, as the
+                    // grid can be smaller than 9x9
+                    let coord = |arg: &str| arg.parse().ok().filter(|&n: &u8| n < 9);
+                    match (coord(&args[0]), coord(&args[1])) {
+                        (Some(row), Some(col)) => Some(Action::FocusCell(row, col)),
+                        _ => None,
+                    }
+                }
                 _ => None,
             }
             .ok_or(ActionParsingError(value.clone()));
@@ -183,6 +572,57 @@ impl TryFrom<String> for Action {
     }
 }
 
+impl fmt::Display for Action {
+    /// Writes the action in config notation, as read by `Action::try_from`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::MoveRow(offset, wrap) => write!(f, "moveRow({offset}, {wrap})"),
+            Action::MoveCol(offset, wrap) => write!(f, "moveCol({offset}, {wrap})"),
+            Action::MoveSkipFilled(direction) => write!(f, "moveSkip({})", direction.name()),
+            Action::NextEmpty => write!(f, "nextEmpty()"),
+            Action::PrevEmpty => write!(f, "prevEmpty()"),
+            Action::SetMark(name) => write!(f, "setMark({name})"),
+            Action::JumpToMark(name) => write!(f, "jumpMark({name})"),
+            Action::FocusCell(row, col) => write!(f, "focusCell({row}, {col})"),
+            Action::NextBox => write!(f, "nextBox()"),
+            Action::PrevBox => write!(f, "prevBox()"),
+            Action::FocusCenter => write!(f, "focusCenter()"),
+            Action::FocusCorner(corner) => write!(f, "focusCorner({})", corner.name()),
+            Action::WriteCell(n) => write!(f, "writeCell({n})"),
+            Action::SetCandidate(n) => write!(f, "setCandidate({n})"),
+            Action::RemoveCandidate(n) => write!(f, "removeCandidate({n})"),
+            Action::ToggleCandidate(n) => write!(f, "toggleCandidate({n})"),
+            Action::ClearCandidates => write!(f, "clearCandidates()"),
+            Action::ClearAllCandidates => write!(f, "clearAllCandidates()"),
+            Action::ClearCell => write!(f, "clearCell()"),
+            Action::FillBoxSingles => write!(f, "fillBoxSingles()"),
+            Action::MarkForcedCandidates => write!(f, "markForced()"),
+            Action::FillCandidates => write!(f, "fillCandidates()"),
+            Action::AutoComplete => write!(f, "autoComplete()"),
+            Action::Hint => write!(f, "hint()"),
+            Action::ClearMistakes => write!(f, "clearMistakes()"),
+            Action::ToggleFlag => write!(f, "toggleFlag()"),
+            Action::CycleColor => write!(f, "cycleColor()"),
+            Action::SetColor(color) => write!(f, "setColor({color})"),
+            Action::ClearAllColors => write!(f, "clearAllColors()"),
+            Action::HighlightCurrentDigit => write!(f, "highlightCurrentDigit()"),
+            Action::HighlightDigit(n) => write!(f, "highlightDigit({n})"),
+            Action::ClearHighlight => write!(f, "clearHighlight()"),
+            Action::SetPencilColor(color) => write!(f, "setPencilColor({color})"),
+            Action::ToggleAutoNotes => write!(f, "toggleAutoNotes()"),
+            Action::ToggleInputMode => write!(f, "toggleInputMode()"),
+            Action::SetErrorMode(mode) => write!(f, "setErrorMode({})", mode.name()),
+            Action::ToggleErrors => write!(f, "toggleErrors()"),
+            Action::ClearAll => write!(f, "clearAll()"),
+            Action::Undo => write!(f, "undo()"),
+            Action::Redo => write!(f, "redo()"),
+            Action::NewGame => write!(f, "newGame()"),
+            Action::Restart => write!(f, "restart()"),
+            Action::ClearBoard => write!(f, "clearBoard()"),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 #[error("Invalid action: {0}")]
 pub struct ActionParsingError(String);
@@ -205,3 +645,67 @@ impl fmt::Display for ActionParsingError {
 
 impl std::error::Error for ActionParsingError {}
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_kind_all() {
+        // JS sees kinds as their discriminant, used as their index in ALL
+        for (i, &kind) in ActionKind::ALL.iter().enumerate() {
+            assert_eq!(kind as usize, i);
+        }
+
+        // Exhaustive so that a new kind doesn't compile until listed here. Only the kind declared
+        // last is true, ALL holding every kind if its length reaches that kind.
+        let is_last = |kind: ActionKind| match kind {
+            ActionKind::ClearBoard => true,
+            ActionKind::MoveRow
+            | ActionKind::MoveCol
+            | ActionKind::MoveSkipFilled
+            | ActionKind::NextEmpty
+            | ActionKind::PrevEmpty
+            | ActionKind::SetMark
+            | ActionKind::JumpToMark
+            | ActionKind::FocusCell
+            | ActionKind::NextBox
+            | ActionKind::PrevBox
+            | ActionKind::FocusCenter
+            | ActionKind::FocusCorner
+            | ActionKind::WriteCell
+            | ActionKind::SetCandidate
+            | ActionKind::RemoveCandidate
+            | ActionKind::ToggleCandidate
+            | ActionKind::ClearCandidates
+            | ActionKind::ClearAllCandidates
+            | ActionKind::ClearCell
+            | ActionKind::FillBoxSingles
+            | ActionKind::MarkForcedCandidates
+            | ActionKind::FillCandidates
+            | ActionKind::AutoComplete
+            | ActionKind::Hint
+            | ActionKind::ClearMistakes
+            | ActionKind::ToggleFlag
+            | ActionKind::CycleColor
+            | ActionKind::SetColor
+            | ActionKind::ClearAllColors
+            | ActionKind::HighlightCurrentDigit
+            | ActionKind::HighlightDigit
+            | ActionKind::ClearHighlight
+            | ActionKind::SetPencilColor
+            | ActionKind::ToggleAutoNotes
+            | ActionKind::ToggleInputMode
+            | ActionKind::SetErrorMode
+            | ActionKind::ToggleErrors
+            | ActionKind::ClearAll
+            | ActionKind::Undo
+            | ActionKind::Redo
+            | ActionKind::NewGame
+            | ActionKind::Restart => false,
+        };
+        let last = ActionKind::ALL[ActionKind::ALL.len() - 1];
+        assert!(is_last(last));
+        assert_eq!(ActionKind::ALL.len(), last as usize + 1);
+    }
+}
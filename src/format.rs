@@ -0,0 +1,104 @@
+use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PuzzleFormatError {
+    #[error("Puzzle must have 81 cells, found {0}")]
+    Length(usize),
+    #[error("Invalid character '{1}' at cell {0}")]
+    Character(usize, char),
+    #[error("Only 9x9 grids can be written as 81 characters, found {0} rows")]
+    GridSize(usize),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for PuzzleFormatError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// Parses a 9x9 puzzle written as 81 characters in reading order. Digits 1-9 are givens, while
+/// `0` and `.` are empty cells. Whitespace is ignored, so the puzzle can be split in lines.
+pub fn parse_81(puzzle: &str) -> Result<Vec<Vec<u8>>, PuzzleFormatError> {
+    let cells: Vec<char> = puzzle.chars().filter(|c| !c.is_whitespace()).collect();
+    if cells.len() != 81 {
+        return Err(PuzzleFormatError::Length(cells.len()));
+    }
+
+    let digits = cells
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| match c {
+            '.' => Ok(0),
+            '0'..='9' => Ok(c as u8 - b'0'),
+            _ => Err(PuzzleFormatError::Character(i, c)),
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    Ok(digits.chunks(9).map(|row| row.to_vec()).collect())
+}
+
+/// Writes a 9x9 grid as 81 characters in reading order, the inverse of `parse_81`. Empty cells
+/// (0) are written as `.`. Errors if the grid isn't 9x9.
+pub fn to_81(grid: &[Vec<u8>]) -> Result<String, PuzzleFormatError> {
+    if grid.len() != 9 || grid.iter().any(|row| row.len() != 9) {
+        return Err(PuzzleFormatError::GridSize(grid.len()));
+    }
+    Ok(grid
+        .iter()
+        .flatten()
+        .map(|&digit| match digit {
+            0 => '.',
+            _ => (b'0' + digit) as char,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_81() {
+        let puzzle = "1.3".to_owned() + &"0".repeat(78);
+        let grid = parse_81(&puzzle).unwrap();
+        assert_eq!(grid.len(), 9);
+        assert_eq!(grid[0][..3], [1, 0, 3]);
+        assert!(grid[1..].iter().all(|r| r.iter().all(|&d| d == 0)));
+
+        // Whitespace is ignored
+        let split: String = puzzle
+            .as_bytes()
+            .chunks(9)
+            .map(|r| String::from_utf8_lossy(r) + "\n")
+            .collect();
+        assert_eq!(parse_81(&split), Ok(grid));
+
+        assert_eq!(parse_81("123"), Err(PuzzleFormatError::Length(3)));
+        let invalid = "x".to_owned() + &"0".repeat(80);
+        assert_eq!(
+            parse_81(&invalid),
+            Err(PuzzleFormatError::Character(0, 'x'))
+        );
+    }
+
+    #[test]
+    fn test_to_81() {
+        let puzzle =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+        let grid = parse_81(puzzle).unwrap();
+        assert_eq!(grid[0], [0, 0, 3, 0, 2, 0, 6, 0, 0]);
+        assert_eq!(to_81(&grid).unwrap(), puzzle);
+
+        // Zeros are written back as dots
+        let zeros = puzzle.replace('.', "0");
+        assert_eq!(to_81(&parse_81(&zeros).unwrap()).unwrap(), puzzle);
+
+        assert_eq!(
+            to_81(&vec![vec![0; 6]; 6]),
+            Err(PuzzleFormatError::GridSize(6))
+        );
+    }
+}
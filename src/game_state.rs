@@ -1,20 +1,41 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    rc::Rc,
+};
 
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use wasm_bindgen::prelude::*;
 use web_sys::{console, KeyboardEvent};
 
 use crate::{
-    actions::Action,
-    generation,
-    key::Key,
-    keybinds::{Keybind, KeybindManager},
+    actions::{Action, ActionKind, Direction, ErrorMode},
+    format::{self, PuzzleFormatError},
+    generation::{
+        self, Board, GenerationError, GenerationOptions, GridShape, Puzzle, PuzzleDecodeError,
+        Symmetry, VerifyError,
+    },
+    key::{Key, KeyParseError},
+    keybinds::{Keybind, KeybindManager, KeybindManagerError},
+    solver::{self, DifficultyReport},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Cell {
     pub digit: Option<u8>,
-    pub candidates: [bool; 9],
+    /// Pencil color of each candidate, index `n` holding the color of candidate `n + 1`. 0 means
+    /// the candidate isn't marked, and `1..=PENCIL_COLOR_COUNT` is one of the pencil colors.
+    pub candidates: [u8; 9],
     pub is_given: bool,
+    /// Marked by the player as uncertain
+    pub flagged: bool,
+    /// Background color set by the player, 0 meaning none and `1..=CELL_COLOR_COUNT` one of the
+    /// palette's colors
+    pub color: u8,
+    /// Digit revealed by the `Hint` action rather than found by the player
+    #[serde(default)]
+    pub is_hint: bool,
 }
 
 impl Default for Cell {
@@ -27,34 +48,567 @@ impl Cell {
     pub fn new() -> Self {
         Cell {
             digit: None,
-            candidates: [false; 9],
+            candidates: [0; 9],
             is_given: false,
+            flagged: false,
+            color: 0,
+            is_hint: false,
         }
     }
 
     pub fn clear_candidates(&mut self) {
-        self.candidates = [false; 9];
+        self.candidates = [0; 9];
+    }
+
+    /// Returns the candidate of an empty cell if it's the only one marked
+    pub fn single_candidate(&self) -> Option<u8> {
+        let mut marked = (1..=9).filter(|&n| self.has_candidate(n));
+        match (marked.next(), marked.next()) {
+            (Some(n), None) if self.digit.is_none() => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Returns true if `digit` is marked as a candidate, whatever its color
+    pub fn has_candidate(&self, digit: u8) -> bool {
+        self.candidates[digit as usize - 1] != 0
+    }
+}
+
+/// Mistake shown on a cell, see `ErrorMode`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellError {
+    /// The digit is also in one of the cell's peers
+    Conflict,
+    /// The digit doesn't match the solution
+    Mismatch,
+}
+
+/// Options changing how the game behaves, without being part of the board's state
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+    pub error_mode: ErrorMode,
+    /// Clears the highlighted digit once all of its occurences are placed on the board
+    pub clear_completed_highlight: bool,
+    /// Keeps the candidates of every empty cell up to date as digits are written and cleared
+    pub auto_notes: bool,
+    /// Removes a written digit from the candidates of its peers, leaving the other candidates
+    /// alone unlike `auto_notes`
+    pub auto_prune_candidates: bool,
+    /// Lets generation go below the minimum given count of a unique puzzle
+    pub allow_non_unique: bool,
+    /// Minimum number of givens generated puzzles keep in every box
+    pub min_clues_per_box: u8,
+    /// Lets `MoveSkipFilled` continue on the next line once it reaches the edge of the grid
+    pub wrap_skip_moves: bool,
+    /// Shows the solution faintly in empty cells, only while in practice mode
+    pub ghost_solution: bool,
+    /// Highlights the digit of the focused cell after every move, or nothing on empty cells
+    pub highlight_follows_focus: bool,
+    /// Makes `set_given` wait for `confirm_pending` before replacing a digit with another one
+    pub editor_confirm_overwrite: bool,
+    /// Pattern formed by the givens of generated puzzles
+    pub symmetry: Symmetry,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            error_mode: ErrorMode::Both,
+            clear_completed_highlight: false,
+            auto_notes: false,
+            auto_prune_candidates: false,
+            allow_non_unique: false,
+            min_clues_per_box: 0,
+            wrap_skip_moves: false,
+            ghost_solution: false,
+            highlight_follows_focus: false,
+            editor_confirm_overwrite: false,
+            symmetry: Symmetry::None,
+        }
+    }
+}
+
+/// Maximum number of entries kept in the input history, older entries get dropped first
+const HISTORY_CAPACITY: usize = 1000;
+
+/// An action applied in response to a key press
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp_ms: f64,
+    pub keybind: Keybind,
+    pub action: Action,
+}
+
+/// Maximum number of marks that can be set at once
+const MAX_MARKS: usize = 10;
+
+/// Ctrl combos left to the browser by default, even when bound, so copy and paste keep working
+const DEFAULT_CTRL_PASSTHROUGH: [Key; 3] = [Key::C, Key::V, Key::X];
+
+/// Returns true if the `keydown` listener should prevent the browser's default handling of
+/// Ctrl+`key`, which is the case when the combo is bound and isn't in `passthrough`
+fn captures_ctrl_combo(kb_manager: &KeybindManager, passthrough: &HashSet<Key>, key: Key) -> bool {
+    let keybind = Keybind {
+        key,
+        modifier: Some(Key::Control),
+    };
+    !passthrough.contains(&key) && kb_manager.get_actions(&keybind).is_some()
+}
+
+/// Resolves the key of a `keydown` event as bound in the keybinds. With `use_key_codes`, the
+/// physical key is used when known, telling numpad digits apart from the top row.
+fn event_key(e: &KeyboardEvent, use_key_codes: bool) -> Key {
+    if use_key_codes {
+        if let Ok(key) = Key::try_from_js_code(e.code()) {
+            return key;
+        }
+    }
+
+    // Shift+digit results in #key returning the associated special character instead of the
+    // digit (meaning S-1..S-9) binds don't work. Add edge case handling
+    if e.shift_key() && (e.code().starts_with("Digit") || e.code().starts_with("Numpad")) {
+        // Edge-case use code (Digitn or Numpadn) to generate Key object
+        let key_digit = e.code().chars().last().unwrap();
+        if key_digit.is_ascii_digit() {
+            return Key::try_from_js(key_digit.to_string()).unwrap();
+        }
+    }
+
+    // Edge-case didn't apply, do normal logic with e.key
+    // Map unknown keys to 0 (probably should warn users in console)
+    Key::try_from_js(e.key()).unwrap_or(Key::Zero)
+}
+
+/// Number of background colors cells can be given by `CycleColor`
+pub const CELL_COLOR_COUNT: u8 = 2;
+
+/// Number of colors candidates can be marked with
+pub const PENCIL_COLOR_COUNT: u8 = 5;
+
+/// Maximum number of entries kept in the undo stack, older entries get dropped first
+const UNDO_CAPACITY: usize = 256;
+
+/// State of the whole board and of the player's progress on it, restored when undoing an
+/// action replacing the board
+#[derive(Clone)]
+struct BoardSnapshot {
+    shape: GridShape,
+    grid: Vec<Vec<Cell>>,
+    solution: Board,
+    moves: u32,
+    mistakes: u32,
+    won: bool,
+    elapsed_ms: f64,
+    /// The timer is resumed from the time of the restore, rather than counting the time spent
+    /// on the other board
+    timer_running: bool,
+}
+
+/// In-progress game as written by `GameState::export_state`
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    size: usize,
+    grid: Vec<Vec<Cell>>,
+    focused: (u8, u8),
+    highlighted_digit: Option<u8>,
+    error_mode: ErrorMode,
+    /// Left out unless asked for, so a shared game doesn't give the answers away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    solution: Option<Vec<Vec<u8>>>,
+}
+
+impl SavedState {
+    /// Returns true if the grid, solution and positions all fit a `size` by `size` board
+    fn is_valid(&self) -> bool {
+        let size = self.size;
+        fn is_square<T>(grid: &[Vec<T>], size: usize) -> bool {
+            grid.len() == size && grid.iter().all(|row| row.len() == size)
+        }
+        let cells_valid = self.grid.iter().flatten().all(|cell| {
+            cell.digit.is_none_or(|n| (1..=size as u8).contains(&n))
+                && cell.candidates[size..].iter().all(|&c| c == 0)
+                && cell.candidates.iter().all(|&c| c <= PENCIL_COLOR_COUNT)
+                && cell.color <= CELL_COLOR_COUNT
+        });
+        let solution_valid = self.solution.as_ref().is_none_or(|solution| {
+            is_square(solution, size)
+                && solution
+                    .iter()
+                    .flatten()
+                    .all(|&n| (1..=size as u8).contains(&n))
+        });
+
+        is_square(&self.grid, size)
+            && cells_valid
+            && solution_valid
+            && (self.focused.0 as usize) < size
+            && (self.focused.1 as usize) < size
+            && self
+                .highlighted_digit
+                .is_none_or(|n| (1..=size as u8).contains(&n))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ImportStateError {
+    #[error("Invalid saved game: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Saved game doesn't fit a supported board")]
+    Shape,
+    #[error("Saved game has no solution")]
+    NoSolution,
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for ImportStateError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// A reversible change to the board
+#[derive(Clone)]
+enum UndoEntry {
+    /// Cells changed by an action, as `(row, col, before, after)`
+    Cells(Vec<(usize, usize, Cell, Cell)>),
+    /// The whole board was replaced by a new game, a restart or a clear. Undoing it brings back
+    /// the previous puzzle as it was.
+    Board {
+        before: Box<BoardSnapshot>,
+        after: Box<BoardSnapshot>,
+    },
+}
+
+#[derive(Default)]
+struct UndoStack {
+    undo: VecDeque<UndoEntry>,
+    redo: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    /// Records a new change, dropping the changes that were undone since they can't be redone
+    /// anymore
+    fn push(&mut self, entry: UndoEntry) {
+        if self.undo.len() == UNDO_CAPACITY {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(entry);
+        self.redo.clear();
+    }
+
+    /// Returns the last change to revert, making it available to redo
+    fn undo(&mut self) -> Option<UndoEntry> {
+        let entry = self.undo.pop_back()?;
+        self.redo.push(entry.clone());
+        Some(entry)
+    }
+
+    /// Returns the last undone change to apply again
+    fn redo(&mut self) -> Option<UndoEntry> {
+        let entry = self.redo.pop()?;
+        self.undo.push_back(entry.clone());
+        Some(entry)
+    }
+
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Settings of the last generated puzzle, reused by the `NewGame` action
+#[derive(Clone)]
+struct GenerationParams {
+    seed: String,
+    given_count: usize,
+    variant: GridVariant,
+    /// Number of games started from the seed with `NewGame`
+    games_started: u32,
+}
+
+/// Returns the current time in milliseconds, as given by `performance.now()`
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map_or(0., |p| p.now())
+}
+
+/// Browser APIs aren't available outside of wasm (e.g. when running tests)
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    0.
+}
+
+/// What writing a digit in a cell does
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputMode {
+    /// Digits are committed to the cell
+    Normal,
+    /// Digits toggle the cell's candidates, like pencil marks
+    Candidate,
+}
+
+/// Grid variants that can be generated
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridVariant {
+    /// 9x9 grid with 3x3 boxes
+    Classic,
+    /// 6x6 grid with 2x3 boxes
+    Six,
+}
+
+impl From<GridVariant> for GridShape {
+    fn from(value: GridVariant) -> Self {
+        match value {
+            GridVariant::Classic => GridShape::CLASSIC,
+            GridVariant::Six => GridShape::SIX,
+        }
+    }
+}
+
+/// Everything needed to set up a game in a single call, see `GameState::from_config_json`.
+/// Options are kept as a raw JSON value so their errors can be reported separately.
+#[derive(Deserialize)]
+struct GameConfig {
+    #[serde(default)]
+    keybinds: String,
+    /// Givens of a 9x9 puzzle in the format read by `format::parse_81`
+    puzzle: Option<String>,
+    options: Option<serde_json::Value>,
+    canvas_id: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum GameConfigError {
+    #[error("Invalid config: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid keybinds: {0}")]
+    Keybinds(#[from] KeybindManagerError),
+    #[error("Invalid puzzle: {0}")]
+    Puzzle(#[from] PuzzleFormatError),
+    #[error("Invalid puzzle: {0}")]
+    Solution(#[from] GenerationError),
+    #[error("Invalid options: {0}")]
+    Options(serde_json::Error),
+    #[error(transparent)]
+    Listener(#[from] ListenerError),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for GameConfigError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// Reasons the key listeners can't be set up on the element receiving key presses
+#[derive(Error, Debug)]
+pub enum ListenerError {
+    #[error("No global window, the game must run in a browser")]
+    Window,
+    #[error("The window has no document")]
+    Document,
+    #[error("No element with id \"{0}\" to listen to")]
+    Element(String),
+    #[error("Couldn't register the key listeners: {0}")]
+    Registration(String),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for ListenerError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Cell ({0}, {1}) is outside of the grid")]
+pub struct CellOutOfRange(u8, u8);
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for CellOutOfRange {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum LoadPuzzleError {
+    #[error("Invalid puzzle: {0}")]
+    Format(#[from] PuzzleFormatError),
+    #[error("Puzzle has no solution, some of its digits contradict each other")]
+    NoSolution,
+    #[error("Puzzle is ambiguous, it has more than one solution")]
+    MultipleSolutions,
+}
+
+impl From<GenerationError> for LoadPuzzleError {
+    fn from(err: GenerationError) -> Self {
+        match err {
+            GenerationError::MultipleSolutions => LoadPuzzleError::MultipleSolutions,
+            _ => LoadPuzzleError::NoSolution,
+        }
+    }
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for LoadPuzzleError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
     }
 }
 
 #[wasm_bindgen]
 pub struct GameState {
-    kb_manager: KeybindManager,
+    /// Shared with the `keydown` listener, so swapping the manager also updates its binds
+    kb_manager: Rc<RefCell<KeybindManager>>,
     last_key: Rc<RefCell<Option<Keybind>>>,
+    /// Keys whose Ctrl combos are never captured by the `keydown` listener
+    ctrl_passthrough: Rc<RefCell<HashSet<Key>>>,
+    /// Makes the `keydown` listener read keys from `KeyboardEvent.code`, see `set_use_key_codes`
+    use_key_codes: Rc<RefCell<bool>>,
+    shape: GridShape,
     grid: Vec<Vec<Cell>>,
-    solution: Vec<Vec<u8>>,
+    solution: Board,
     focused_row: u8,
     focused_col: u8,
     highlighted_digit: Option<u8>,
-    // Refactor game options into their own struct
-    show_errors: bool,
+    options: GameOptions,
+    history: VecDeque<HistoryEntry>,
+    /// Set when the last applied action couldn't do anything because of the board's state
+    last_action_rejected: bool,
+    input_mode: InputMode,
+    /// Learning mode where assists revealing the solution are allowed
+    practice_mode: bool,
+    /// Kinds of actions ignored by `update`, none by default
+    disallowed_actions: HashSet<ActionKind>,
+    undo_stack: UndoStack,
+    generation_params: Option<GenerationParams>,
+    /// Named focus positions, set and jumped to like vim marks
+    marks: HashMap<char, (u8, u8)>,
+    /// Set when the board changed since the last `mark_saved` call
+    dirty: bool,
+    /// Color given to the candidates marked by the player
+    pencil_color: u8,
+    /// Number of actions that changed the board since the puzzle was loaded
+    moves: u32,
+    /// Number of wrong digits written since the puzzle was loaded, see `mistakes`
+    mistakes: u32,
+    /// `set_given` call waiting for `confirm_pending`, as `(row, col, digit)`
+    pending_overwrite: Option<(u8, u8, u8)>,
+    /// Set by `update` once the board is complete, see `has_won`
+    won: bool,
+    /// Time at which the timer was last started or ticked, `None` while it's stopped
+    timer_start_ms: Option<f64>,
+    /// Time accumulated by the timer up to `timer_start_ms`
+    elapsed_ms: f64,
+    /// Error mode restored when showing errors again, see `set_show_errors`
+    shown_error_mode: ErrorMode,
 }
 
 impl GameState {
+    /// Creates a new `GameState` object with an empty 9x9 grid, without registering any event
+    /// listener. Key presses must then be fed to `last_key` by other means.
+    pub fn new(kb_manager: KeybindManager) -> Self {
+        Self {
+            kb_manager: Rc::new(RefCell::new(kb_manager)),
+            last_key: Rc::new(RefCell::new(None)),
+            ctrl_passthrough: Rc::new(RefCell::new(HashSet::from(DEFAULT_CTRL_PASSTHROUGH))),
+            use_key_codes: Rc::new(RefCell::new(false)),
+            shape: GridShape::CLASSIC,
+            grid: vec![vec![Cell::new(); 9]; 9],
+            solution: [[0; 9]; 9],
+            focused_row: 0,
+            focused_col: 0,
+            highlighted_digit: None,
+            options: GameOptions::default(),
+            history: VecDeque::new(),
+            last_action_rejected: false,
+            input_mode: InputMode::Normal,
+            practice_mode: false,
+            disallowed_actions: HashSet::new(),
+            undo_stack: UndoStack::default(),
+            generation_params: None,
+            marks: HashMap::new(),
+            dirty: false,
+            pencil_color: 1,
+            moves: 0,
+            mistakes: 0,
+            pending_overwrite: None,
+            won: false,
+            timer_start_ms: None,
+            elapsed_ms: 0.,
+            shown_error_mode: ErrorMode::Both,
+        }
+    }
+
+    /// Builds a state from a JSON config, without registering any event listener. Returns the
+    /// state along with the id of the element its listeners should be registered on.
+    fn from_config(json: &str) -> Result<(Self, String), GameConfigError> {
+        let config: GameConfig = serde_json::from_str(json)?;
+
+        let kb_manager = KeybindManager::with_config(&config.keybinds)?;
+        let mut state = Self::new(kb_manager);
+
+        if let Some(options) = config.options {
+            state.options = serde_json::from_value(options).map_err(GameConfigError::Options)?;
+        }
+
+        if let Some(puzzle) = config.puzzle {
+            let givens = format::parse_81(&puzzle)?;
+            state.load_givens(&givens)?;
+        }
+
+        let canvas_id = config.canvas_id.unwrap_or_else(|| "canvas".to_owned());
+        Ok((state, canvas_id))
+    }
+
+    /// Replaces the board with a 9x9 puzzle made of the non-zero digits of `givens`, and solves
+    /// it. The board is left untouched if the puzzle doesn't have a unique solution.
+    fn load_givens(&mut self, givens: &[Vec<u8>]) -> Result<(), GenerationError> {
+        let solution = generation::unique_solution(givens, GridShape::CLASSIC)?;
+        self.set_classic_board(givens, solution);
+        Ok(())
+    }
+
+    /// Replaces the board with the givens of a 9x9 puzzle, along with its solution
+    fn set_classic_board(&mut self, givens: &[Vec<u8>], solution: Board) {
+        self.undo_stack.clear();
+        self.dirty = true;
+        self.reset_progress();
+        self.shape = GridShape::CLASSIC;
+        self.grid = vec![vec![Cell::new(); 9]; 9];
+        for (row, row_vec) in givens.iter().enumerate() {
+            for (col, &digit) in row_vec.iter().enumerate() {
+                self.set_given(row, col, digit);
+            }
+        }
+        self.solution = solution;
+    }
+
     pub fn grid(&self) -> &Vec<Vec<Cell>> {
         &self.grid
     }
 
+    pub fn shape(&self) -> GridShape {
+        self.shape
+    }
+
+    /// Number of rows and columns of the grid
+    pub fn size(&self) -> usize {
+        self.shape.size()
+    }
+
     pub fn focused_cell_coord(&self) -> (u8, u8) {
         (self.focused_row, self.focused_col)
     }
@@ -63,8 +617,22 @@ impl GameState {
         self.highlighted_digit
     }
 
-    pub fn show_errors(&self) -> bool {
-        self.show_errors
+    /// Returns true if the cell holds the highlighted digit
+    pub fn is_digit_highlighted(&self, row: usize, col: usize) -> bool {
+        self.highlighted_digit.is_some() && self.grid[row][col].digit == self.highlighted_digit
+    }
+
+    /// Returns true if the highlighted digit is marked as a candidate of the empty cell
+    pub fn has_highlighted_candidate(&self, row: usize, col: usize) -> bool {
+        let cell = &self.grid[row][col];
+        cell.digit.is_none()
+            && self
+                .highlighted_digit
+                .is_some_and(|n| cell.has_candidate(n))
+    }
+
+    pub fn error_mode(&self) -> ErrorMode {
+        self.options.error_mode
     }
 
     pub fn expected_value(&self, row: usize, col: usize) -> u8 {
@@ -103,221 +671,3427 @@ impl GameState {
     fn get_mut_focused_cell(&mut self) -> &mut Cell {
         &mut self.grid[self.focused_row as usize][self.focused_col as usize]
     }
-}
-
-// Methods exported to JS
-#[wasm_bindgen]
-impl GameState {
-    /// Creates a new `GameState` object and registers a `keydown` event listener
-    pub fn with_keybind_manager(kb_manager: KeybindManager) -> Self {
-        let last_key_mtx: Rc<RefCell<Option<Keybind>>> = Rc::new(RefCell::new(None));
 
-        let kb_callback;
+    /// Returns how many times each digit is currently placed on the board, givens included.
+    /// Index `n` holds the count for digit `n + 1`.
+    pub fn digit_counts(&self) -> [u8; 9] {
+        let mut counts = [0; 9];
+        for digit in self
+            .grid
+            .iter()
+            .flat_map(|r| r.iter())
+            .filter_map(|c| c.digit)
         {
-            let last_key_mtx = last_key_mtx.clone();
-            let kb_manager = kb_manager.clone();
+            counts[digit as usize - 1] += 1;
+        }
+        counts
+    }
 
-            kb_callback = Closure::wrap(Box::new(move |e: KeyboardEvent| {
-                loop {
-                    if e.ctrl_key() {
-                        // Block event if a keybind is registered with same key
-                        let keybind = Keybind {
-                            key: Key::try_from_js(e.key()).unwrap_or(Key::Zero),
-                            modifier: Some(Key::Control),
-                        };
-                        if kb_manager.get_action(&keybind).is_some() {
-                            e.prevent_default();
-                        }
-                    }
+    /// Returns which digits are placed on every row of the board (9 times for a 9x9 grid),
+    /// index `n` being digit `n + 1`
+    pub fn completed_digits(&self) -> [bool; 9] {
+        self.digit_counts()
+            .map(|count| count as usize >= self.size())
+    }
 
-                    // Loop until we can obtain a mutable borrow
-                    if let Ok(mut x) = last_key_mtx.try_borrow_mut() {
-                        // Shift+digit results in #key returning the associated special character
-                        // instead of the digit (meaning S-1..S-9) binds don't work. Add
-                        // edge case handling
-                        let mut key: Option<Key> = None;
-                        if e.shift_key()
-                            && (e.code().starts_with("Digit") || e.code().starts_with("Numpad"))
-                        {
-                            // Edge-case use code (Digitn or Numpadn) to generate Key object
-                            let key_digit = e.code().chars().last().unwrap();
-                            if key_digit.is_ascii_digit() {
-                                key = Some(Key::try_from_js(key_digit.to_string()).unwrap());
-                            }
-                        }
+    /// Returns the cells sharing a row, column or box with the specified cell, excluding the
+    /// cell itself
+    pub fn peers(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.shape.peers(row, col)
+    }
 
-                        if key.is_none() {
-                            // Edge-case didn't apply, do normal logic with e.key
-                            // Map unknown keys to 0 (probably should warn users in console)
-                            key = Some(Key::try_from_js(e.key()).unwrap_or(Key::Zero));
-                        }
-                        let key = key.unwrap();
+    /// Returns the digits that can be placed in a cell without conflicting with the digits of
+    /// its peers. Index `n` is true if digit `n + 1` can be placed.
+    pub fn legal_digits(&self, row: usize, col: usize) -> [bool; 9] {
+        let mut legal = [false; 9];
+        legal[..self.size()].fill(true);
+        for (r, c) in self.peers(row, col) {
+            if let Some(n) = self.grid[r][c].digit {
+                legal[n as usize - 1] = false;
+            }
+        }
+        legal
+    }
 
-                        // If key event is for pressing down on a modifier key, ignore
-                        // (as in: ignore when user presses down on Shift itself, as shift cannot
-                        // be binded by itself)
-                        if key.is_modifier() {
-                            break;
-                        }
+    /// Returns true if the cell holds a digit also present in one of its peers
+    pub fn has_conflict(&self, row: usize, col: usize) -> bool {
+        match self.grid[row][col].digit {
+            Some(n) => self
+                .peers(row, col)
+                .iter()
+                .any(|&(r, c)| self.grid[r][c].digit == Some(n)),
+            None => false,
+        }
+    }
 
-                        let modifier = if e.shift_key() {
-                            Some(Key::Shift)
-                        } else if e.ctrl_key() {
-                            Some(Key::Control)
-                        } else if e.alt_key() {
-                            Some(Key::Alt)
-                        } else if e.meta_key() {
-                            Some(Key::Meta)
-                        } else {
-                            None
-                        };
+    /// Restricts the actions applied by `update` to the specified kinds, e.g. to only allow
+    /// navigation in a demo. Keybinds of other actions are still parsed, but ignored.
+    pub fn set_allowed_actions(&mut self, kinds: &[ActionKind]) {
+        self.disallowed_actions = ActionKind::ALL
+            .into_iter()
+            .filter(|kind| !kinds.contains(kind))
+            .collect();
+    }
 
-                        *x = Some(Keybind { key, modifier });
-                        console::debug_1(&format!("{:?} (k:{},c:{})", x, e.key(), e.code()).into());
-                        break;
-                    }
-                }
-            }) as Box<dyn FnMut(_)>);
+    /// Returns the solution digit to show faintly in a cell, if the cell is empty and the
+    /// ghost solution is enabled in practice mode
+    pub fn ghost_digit(&self, row: usize, col: usize) -> Option<u8> {
+        let enabled = self.practice_mode && self.options.ghost_solution;
+        (enabled && self.grid[row][col].digit.is_none()).then(|| self.solution[row][col])
+    }
+
+    /// Returns the peers of a cell already containing `digit`, i.e. the cells preventing `digit`
+    /// from being placed in the cell. Empty for out of range coordinates or digits.
+    pub fn conflicting_peers(&self, row: u8, col: u8, digit: u8) -> Vec<(u8, u8)> {
+        let (row, col) = (row as usize, col as usize);
+        if row >= self.size() || col >= self.size() || !(1..=self.size()).contains(&digit.into()) {
+            return Vec::new();
         }
 
-        // Register callback on "keydown" event on canvas element
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas = document.get_element_by_id("canvas").unwrap();
+        self.peers(row, col)
+            .into_iter()
+            .filter(|&(r, c)| self.grid[r][c].digit == Some(digit))
+            .map(|(r, c)| (r as u8, c as u8))
+            .collect()
+    }
 
-        canvas
-            .add_event_listener_with_callback("keydown", kb_callback.as_ref().unchecked_ref())
-            .unwrap();
+    /// Returns the next empty cell from the focused cell in a direction. When wrapping, the
+    /// search continues on the next line (next row for horizontal directions, next column for
+    /// vertical ones) and around the grid, until every other cell was looked at.
+    fn next_empty_in_direction(&self, direction: Direction) -> Option<(usize, usize)> {
+        let size = self.size();
+        let (d_row, d_col) = direction.offset();
+        let (mut row, mut col) = (self.focused_row as usize, self.focused_col as usize);
 
-        // Callback closure needs to outlive this method call.
-        // "Forget" the object so that rust doesn't destroy it
-        kb_callback.forget();
+        for _ in 1..size * size {
+            let next_row = row as isize + d_row as isize;
+            let next_col = col as isize + d_col as isize;
+            let in_grid =
+                (0..size as isize).contains(&next_row) && (0..size as isize).contains(&next_col);
 
-        Self {
-            kb_manager,
-            last_key: last_key_mtx,
-            grid: vec![vec![Cell::new(); 9]; 9],
-            solution: vec![vec![0; 9]; 9],
-            focused_row: 0,
-            focused_col: 0,
-            highlighted_digit: None,
-            show_errors: true,
+            (row, col) = match (in_grid, self.options.wrap_skip_moves) {
+                (true, _) => (next_row as usize, next_col as usize),
+                (false, false) => return None,
+                // Go to the start of the next line, wrapping around the grid
+                (false, true) => match direction {
+                    Direction::Right => ((row + 1) % size, 0),
+                    Direction::Left => ((row + size - 1) % size, size - 1),
+                    Direction::Down => (0, (col + 1) % size),
+                    Direction::Up => (size - 1, (col + size - 1) % size),
+                },
+            };
+
+            if self.grid[row][col].digit.is_none() {
+                return Some((row, col));
+            }
         }
+        None
     }
 
-    /// Updates the game state based on the user's inputs
-    pub fn update(&mut self) {
-        if let Some(keybind) = self.consume_last_key() {
-            if let Some(action) = self.kb_manager.get_action(&keybind) {
-                match action {
-                    Action::MoveRow(n, safe) => {
-                        // If safe, only move if not out of bounds
-                        // If not safe, move and cap to border of grid if overflow
-                        let new_pos = self.focused_row as i8 + n;
-                        if *safe {
-                            if (0..9).contains(&new_pos) {
-                                self.focused_row = new_pos as u8;
-                            }
-                        } else {
-                            self.focused_row = new_pos.clamp(0, 8) as u8;
-                        }
-                    }
-                    Action::MoveCol(n, safe) => {
-                        // Safe same as MoveRow
-                        let new_pos = self.focused_col as i8 + n;
-                        if *safe {
-                            if (0..9).contains(&new_pos) {
-                                self.focused_col = new_pos as u8;
-                            }
-                        } else {
-                            self.focused_col = new_pos.clamp(0, 8) as u8;
-                        }
-                    }
-                    Action::WriteCell(n) => {
-                        if self.get_focused_cell().is_given {
-                            return;
-                        }
-                        self.get_mut_focused_cell().digit = Some(*n);
-                        self.get_mut_focused_cell().clear_candidates();
-                    }
-                    Action::ClearCell => {
-                        if self.get_focused_cell().is_given {
-                            return;
-                        }
-                        self.get_mut_focused_cell().digit = None
-                    }
-                    Action::SetCandidate(n) => {
-                        if self.get_focused_cell().is_given
-                            || self.get_focused_cell().digit.is_some()
-                        {
-                            return;
-                        }
+    /// Returns the next empty cell after the focused cell in reading order, or the previous one
+    /// if `forward` is false. The search always wraps around the grid.
+    fn next_empty_in_order(&self, forward: bool) -> Option<(usize, usize)> {
+        let size = self.size();
+        let cell_count = size * size;
+        let focused = self.focused_row as usize * size + self.focused_col as usize;
 
-                        let n = *n as usize - 1;
-                        self.get_mut_focused_cell().candidates[n] = true
-                    }
-                    Action::RemoveCandidate(n) => {
-                        if self.get_focused_cell().is_given
-                            || self.get_focused_cell().digit.is_some()
-                        {
-                            return;
-                        }
-                        let n = *n as usize - 1;
-                        self.get_mut_focused_cell().candidates[n] = false
-                    }
-                    Action::ToggleCandidate(n) => {
-                        if self.get_focused_cell().is_given
-                            || self.get_focused_cell().digit.is_some()
-                        {
-                            return;
-                        }
-                        let n = *n as usize - 1;
-                        let curr_val = self.get_mut_focused_cell().candidates[n];
-                        self.get_mut_focused_cell().candidates[n] = !curr_val;
-                    }
-                    Action::ClearCandidates => {
-                        self.get_mut_focused_cell().clear_candidates();
-                    }
-                    Action::HighlightCurrentDigit => {
-                        self.highlighted_digit = self.get_focused_cell().digit;
-                    }
-                    Action::HighlightDigit(n) => {
-                        self.highlighted_digit = Some(*n);
-                    }
-                    Action::ClearHighlight => {
-                        self.highlighted_digit = None;
-                    }
-                    _ => todo!("Remaining actions: {:?}", action),
+        (1..cell_count)
+            .map(|offset| {
+                if forward {
+                    (focused + offset) % cell_count
+                } else {
+                    (focused + cell_count - offset) % cell_count
                 }
-            }
-        }
+            })
+            .map(|i| (i / size, i % size))
+            .find(|&(row, col)| self.grid[row][col].digit.is_none())
     }
-    pub fn generate_grid(&mut self, seed: String, given_count: usize) {
-        let (solution, grid) = generation::generate_grid(seed, given_count);
 
-        // Map grid u8 to Cell
-        let grid = grid
-            .into_iter()
-            .map(|r| {
-                r.into_iter()
-                    .map(|n| match n {
-                        // 0 means masked cell
-                        0 => Cell {
-                            digit: None,
-                            candidates: [false; 9],
-                            is_given: false,
-                        },
-                        // Other digit means given cell
-                        n => Cell {
-                            digit: Some(n),
-                            candidates: [false; 9],
-                            is_given: true,
-                        },
-                    })
-                    .collect()
+    /// Returns true if the cell holds a digit different from the solution. Always false while
+    /// the solution is unknown.
+    pub fn has_mismatch(&self, row: usize, col: usize) -> bool {
+        let expected = self.solution[row][col];
+        expected != 0 && self.grid[row][col].digit.is_some_and(|n| n != expected)
+    }
+
+    /// Returns true if the player entered a digit different from the solution in the cell.
+    /// Givens are never wrong, even when the solution is out of date after editing them.
+    pub fn is_cell_wrong(&self, row: usize, col: usize) -> bool {
+        !self.grid[row][col].is_given && self.has_mismatch(row, col)
+    }
+
+    /// Returns true if every cell holds the digit of the solution. Always false while the
+    /// solution is unknown.
+    pub fn is_complete(&self) -> bool {
+        self.grid
+            .iter()
+            .zip(&self.solution)
+            .all(|(row, expected_row)| {
+                row.iter()
+                    .zip(expected_row)
+                    .all(|(cell, &expected)| expected != 0 && cell.digit == Some(expected))
             })
-            .collect();
-        self.grid = grid;
+    }
 
-        self.solution = solution;
+    /// Returns the number of cells where the player entered a wrong digit, see `is_cell_wrong`
+    pub fn mismatch_count(&self) -> usize {
+        let size = self.size();
+        (0..size)
+            .flat_map(|row| (0..size).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.is_cell_wrong(row, col))
+            .count()
+    }
+
+    /// Returns the mistake to show on a cell according to the error mode. Solution mismatches
+    /// take precedence over conflicts when both are shown.
+    pub fn cell_error(&self, row: usize, col: usize) -> Option<CellError> {
+        let mode = self.options.error_mode;
+        if mode.shows_mismatches() && self.is_cell_wrong(row, col) {
+            Some(CellError::Mismatch)
+        } else if mode.shows_conflicts() && self.has_conflict(row, col) {
+            Some(CellError::Conflict)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the cells without a digit, in reading order
+    pub fn empty_cells(&self) -> Vec<(u8, u8)> {
+        let size = self.size();
+        (0..size)
+            .flat_map(|row| (0..size).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.grid[row][col].digit.is_none())
+            .map(|(row, col)| (row as u8, col as u8))
+            .collect()
+    }
+
+    /// Returns the cells flagged as uncertain by the player, in reading order
+    pub fn flagged_cells(&self) -> Vec<(usize, usize)> {
+        let size = self.size();
+        (0..size)
+            .flat_map(|row| (0..size).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.grid[row][col].flagged)
+            .collect()
+    }
+
+    /// Returns every cell holding a digit also present in one of its peers, regardless of the
+    /// error mode. Givens and entered digits are checked alike, and no solution is needed, so this
+    /// also works for imported puzzles whose solution isn't known.
+    pub fn conflicts(&self) -> Vec<(usize, usize)> {
+        let size = self.size();
+        (0..size)
+            .flat_map(|row| (0..size).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.has_conflict(row, col))
+            .collect()
+    }
+
+    /// Replaces the candidates of the specified cells by their legal digits, skipping filled
+    /// cells. Candidates that were already marked keep their color, new ones use the current
+    /// pencil color.
+    fn recompute_candidates(&mut self, cells: &[(usize, usize)]) {
+        for &(row, col) in cells {
+            if self.grid[row][col].digit.is_none() {
+                let legal = self.legal_digits(row, col);
+                let pencil_color = self.pencil_color;
+                let cell = &mut self.grid[row][col];
+                for (color, is_legal) in cell.candidates.iter_mut().zip(legal) {
+                    *color = match (is_legal, *color) {
+                        (false, _) => 0,
+                        (true, 0) => pencil_color,
+                        (true, color) => color,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Writes a digit in a non-given cell as the player, counting it as a mistake if it doesn't
+    /// match the solution and updating the candidates around it
+    fn write_digit(&mut self, row: usize, col: usize, digit: u8) {
+        let expected = self.expected_value(row, col);
+        let cell = &mut self.grid[row][col];
+        // Writing the same wrong digit again isn't a new mistake
+        if expected != 0 && digit != expected && cell.digit != Some(digit) {
+            self.mistakes += 1;
+        }
+
+        cell.digit = Some(digit);
+        cell.is_hint = false;
+        cell.clear_candidates();
+
+        // Nothing left to find for the highlighted digit once all are placed
+        if self.options.clear_completed_highlight
+            && self.highlighted_digit == Some(digit)
+            && self.digit_counts()[digit as usize - 1] as usize == self.size()
+        {
+            self.highlighted_digit = None;
+        }
+
+        self.prune_peer_candidates(row, col, digit);
+        if self.options.auto_notes {
+            self.recompute_candidates(&self.peers(row, col));
+        }
+    }
+
+    /// Removes `digit` from the candidates of the peers of a cell, if `auto_prune_candidates` is
+    /// enabled
+    fn prune_peer_candidates(&mut self, row: usize, col: usize, digit: u8) {
+        if self.options.auto_prune_candidates {
+            for (r, c) in self.peers(row, col) {
+                self.grid[r][c].candidates[digit as usize - 1] = 0;
+            }
+        }
+    }
+
+    /// Sets a cell as a given, or as a regular empty cell for a digit of 0
+    fn write_given(&mut self, row: usize, col: usize, digit: u8) {
+        self.dirty = true;
+        self.grid[row][col] = match digit {
+            0 => Cell::new(),
+            n => Cell {
+                digit: Some(n),
+                candidates: [0; 9],
+                is_given: true,
+                flagged: false,
+                color: 0,
+                is_hint: false,
+            },
+        };
+    }
+
+    /// Returns the digit of every cell, 0 meaning an empty cell
+    fn digits(&self) -> Vec<Vec<u8>> {
+        self.grid
+            .iter()
+            .map(|r| r.iter().map(|c| c.digit.unwrap_or(0)).collect())
+            .collect()
+    }
+
+    /// Returns the digits of the givens, 0 meaning a cell that isn't a given
+    fn givens(&self) -> Vec<Vec<u8>> {
+        self.grid
+            .iter()
+            .map(|r| {
+                r.iter()
+                    .map(|c| if c.is_given { c.digit.unwrap_or(0) } else { 0 })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the actions applied from key presses, from oldest to newest. Only the last
+    /// `HISTORY_CAPACITY` entries are kept.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.iter().cloned().collect()
+    }
+
+    /// Returns a short description of every cell in row-major order, meant for the
+    /// `aria-label` of the cells of a DOM renderer, e.g. "5, given", "3, entered, conflict" or
+    /// "empty". The position is left to the grid's `aria-rowindex` and `aria-colindex`.
+    pub fn aria_grid_state(&self) -> Vec<String> {
+        let size = self.size();
+        (0..size)
+            .flat_map(|row| (0..size).map(move |col| (row, col)))
+            .map(|(row, col)| {
+                let cell = &self.grid[row][col];
+                let Some(digit) = cell.digit else {
+                    return "empty".to_owned();
+                };
+                let status = match cell {
+                    Cell { is_given: true, .. } => "given",
+                    Cell { is_hint: true, .. } => "hint",
+                    _ => "entered",
+                };
+                if self.has_conflict(row, col) {
+                    format!("{digit}, {status}, conflict")
+                } else {
+                    format!("{digit}, {status}")
+                }
+            })
+            .collect()
+    }
+
+    fn record_history(&mut self, keybind: Keybind, action: Action) {
+        if self.history.len() == HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+        self.history.push_back(HistoryEntry {
+            timestamp_ms: now_ms(),
+            keybind,
+            action,
+        });
+    }
+
+    /// Resets the stats of the game in progress, when starting a puzzle from scratch
+    fn reset_progress(&mut self) {
+        self.moves = 0;
+        self.mistakes = 0;
+        self.won = false;
+        self.timer_start_ms = None;
+        self.elapsed_ms = 0.;
+    }
+
+    fn start_timer_at(&mut self, now_ms: f64) {
+        if self.timer_start_ms.is_none() && !self.won {
+            self.timer_start_ms = Some(now_ms);
+        }
+    }
+
+    fn tick_timer_at(&mut self, now_ms: f64) {
+        if let Some(start_ms) = self.timer_start_ms {
+            self.elapsed_ms += (now_ms - start_ms).max(0.);
+            // The clock stops once the puzzle is solved
+            self.timer_start_ms = (!self.won).then_some(now_ms);
+        }
+    }
+
+    fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            shape: self.shape,
+            grid: self.grid.clone(),
+            solution: self.solution,
+            moves: self.moves,
+            mistakes: self.mistakes,
+            won: self.won,
+            elapsed_ms: self.elapsed_ms,
+            timer_running: self.timer_start_ms.is_some(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &BoardSnapshot) {
+        self.shape = snapshot.shape;
+        self.grid = snapshot.grid.clone();
+        self.solution = snapshot.solution;
+        self.moves = snapshot.moves;
+        self.mistakes = snapshot.mistakes;
+        self.won = snapshot.won;
+        self.elapsed_ms = snapshot.elapsed_ms;
+        self.timer_start_ms = snapshot.timer_running.then(now_ms);
+        self.clamp_focus();
+    }
+
+    /// Replaces the board with a generated grid, returning its number of givens. When the
+    /// puzzle couldn't be masked down to `given_count`, the closest puzzle is loaded and its
+    /// number of givens, above `given_count`, is returned. The board is left untouched if
+    /// generation fails.
+    fn generate_board(
+        &mut self,
+        seed: String,
+        given_count: usize,
+        variant: GridVariant,
+    ) -> Result<usize, GenerationError> {
+        let shape = GridShape::from(variant);
+        let options = GenerationOptions {
+            shape,
+            allow_non_unique: self.options.allow_non_unique,
+            min_clues_per_box: self.options.min_clues_per_box,
+            symmetry: self.options.symmetry,
+        };
+        let (solution, grid, actual_count) =
+            match generation::generate_grid(seed, given_count, &options) {
+                // Still a valid puzzle, only with more givens than asked for
+                Err(GenerationError::CouldNotReachGivenCount {
+                    reached,
+                    givens,
+                    solution,
+                    ..
+                }) => (solution, givens, reached),
+                result => result?,
+            };
+
+        // Map grid u8 to Cell
+        let grid = grid
+            .into_iter()
+            .map(|r| {
+                r.into_iter()
+                    .map(|n| match n {
+                        // 0 means masked cell
+                        0 => Cell {
+                            digit: None,
+                            candidates: [0; 9],
+                            is_given: false,
+                            flagged: false,
+                            color: 0,
+                            is_hint: false,
+                        },
+                        // Other digit means given cell
+                        n => Cell {
+                            digit: Some(n),
+                            candidates: [0; 9],
+                            is_given: true,
+                            flagged: false,
+                            color: 0,
+                            is_hint: false,
+                        },
+                    })
+                    .collect()
+            })
+            .collect();
+        self.grid = grid;
+
+        self.solution = generation::board_from_grid(&solution);
+        self.shape = shape;
+
+        // Focus might be outside of a smaller grid
+        self.clamp_focus();
+
+        Ok(actual_count)
+    }
+
+    /// Keeps the focus inside of the grid after its size changed
+    fn clamp_focus(&mut self) {
+        let max = self.size() as u8 - 1;
+        self.focused_row = self.focused_row.min(max);
+        self.focused_col = self.focused_col.min(max);
+    }
+
+    /// Reverts the last change to the board
+    fn undo(&mut self) {
+        match self.undo_stack.undo() {
+            Some(UndoEntry::Cells(changes)) => {
+                for (row, col, before, _) in changes {
+                    // Givens are never altered by undo
+                    if !self.grid[row][col].is_given {
+                        self.grid[row][col] = before;
+                    }
+                }
+            }
+            Some(UndoEntry::Board { before, .. }) => self.restore(&before),
+            None => self.last_action_rejected = true,
+        }
+    }
+
+    /// Applies the last undone change again
+    fn redo(&mut self) {
+        match self.undo_stack.redo() {
+            Some(UndoEntry::Cells(changes)) => {
+                for (row, col, _, after) in changes {
+                    if !self.grid[row][col].is_given {
+                        self.grid[row][col] = after;
+                    }
+                }
+            }
+            Some(UndoEntry::Board { after, .. }) => self.restore(&after),
+            None => self.last_action_rejected = true,
+        }
+    }
+
+    /// Applies the effects of an action to the game state, recording the changes to the board
+    /// so they can be undone
+    fn apply_action(&mut self, action: &Action) {
+        match action {
+            Action::Undo => {
+                self.last_action_rejected = false;
+                self.undo();
+                self.dirty |= !self.last_action_rejected;
+            }
+            Action::Redo => {
+                self.last_action_rejected = false;
+                self.redo();
+                self.dirty |= !self.last_action_rejected;
+            }
+            Action::NewGame | Action::Restart | Action::ClearBoard => {
+                // Count the time spent until now in the snapshot
+                self.tick_timer_at(now_ms());
+                let before = self.snapshot();
+                self.apply_board_action(action);
+                if !self.last_action_rejected {
+                    // Playing from scratch again
+                    self.reset_progress();
+                    self.undo_stack.push(UndoEntry::Board {
+                        before: Box::new(before),
+                        after: Box::new(self.snapshot()),
+                    });
+                    self.dirty = true;
+                }
+            }
+            _ => {
+                let before = self.grid.clone();
+                self.apply_cell_action(action);
+
+                let changes: Vec<(usize, usize, Cell, Cell)> = before
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(row, row_vec)| {
+                        row_vec
+                            .into_iter()
+                            .enumerate()
+                            .map(move |(col, cell)| (row, col, cell))
+                    })
+                    .filter(|&(row, col, cell)| self.grid[row][col] != cell)
+                    .map(|(row, col, cell)| (row, col, cell, self.grid[row][col]))
+                    .collect();
+                if !changes.is_empty() {
+                    self.undo_stack.push(UndoEntry::Cells(changes));
+                    self.dirty = true;
+                    self.moves += 1;
+                }
+            }
+        }
+    }
+
+    /// Applies an action replacing the whole board
+    fn apply_board_action(&mut self, action: &Action) {
+        self.last_action_rejected = false;
+        match action {
+            Action::NewGame => {
+                let Some(params) = self.generation_params.as_mut() else {
+                    // Nothing was generated yet, the settings of the new game are unknown
+                    self.last_action_rejected = true;
+                    return;
+                };
+                params.games_started += 1;
+                let seed = format!("{}-{}", params.seed, params.games_started);
+                let (given_count, variant) = (params.given_count, params.variant);
+                if self.generate_board(seed, given_count, variant).is_err() {
+                    self.last_action_rejected = true;
+                }
+            }
+            Action::Restart => {
+                for cell in self.grid.iter_mut().flatten() {
+                    if !cell.is_given {
+                        *cell = Cell::new();
+                    }
+                }
+            }
+            Action::ClearBoard => {
+                let size = self.size();
+                self.grid = vec![vec![Cell::new(); size]; size];
+                self.solution = [[0; 9]; 9];
+            }
+            _ => unreachable!("Not a board action: {:?}", action),
+        }
+    }
+
+    /// Updates the state depending on the focused cell after a move
+    fn on_focus_moved(&mut self) {
+        if self.options.highlight_follows_focus {
+            self.highlighted_digit = self.get_focused_cell().digit;
+        }
+    }
+
+    /// Applies the effects of an action only changing cells or the UI state
+    fn apply_cell_action(&mut self, action: &Action) {
+        // Ignore digits that don't exist in smaller grid variants
+        if action.digit().is_some_and(|n| n as usize > self.size()) {
+            return;
+        }
+
+        self.last_action_rejected = false;
+        let size = self.size() as i8;
+        match action {
+            Action::MoveRow(n, safe) => {
+                // If safe, only move if not out of bounds
+                // If not safe, move and cap to border of grid if overflow
+                let new_pos = self.focused_row as i8 + n;
+                if *safe {
+                    if (0..size).contains(&new_pos) {
+                        self.focused_row = new_pos as u8;
+                    }
+                } else {
+                    self.focused_row = new_pos.clamp(0, size - 1) as u8;
+                }
+                self.on_focus_moved();
+            }
+            Action::MoveCol(n, safe) => {
+                // Safe same as MoveRow
+                let new_pos = self.focused_col as i8 + n;
+                if *safe {
+                    if (0..size).contains(&new_pos) {
+                        self.focused_col = new_pos as u8;
+                    }
+                } else {
+                    self.focused_col = new_pos.clamp(0, size - 1) as u8;
+                }
+                self.on_focus_moved();
+            }
+            Action::MoveSkipFilled(direction) => {
+                // Stay in place if there's no empty cell to go to
+                if let Some((row, col)) = self.next_empty_in_direction(*direction) {
+                    self.focused_row = row as u8;
+                    self.focused_col = col as u8;
+                    self.on_focus_moved();
+                }
+            }
+            Action::NextEmpty | Action::PrevEmpty => {
+                let forward = matches!(action, Action::NextEmpty);
+                // Stay in place if there's no other empty cell
+                if let Some((row, col)) = self.next_empty_in_order(forward) {
+                    self.focused_row = row as u8;
+                    self.focused_col = col as u8;
+                    self.on_focus_moved();
+                }
+            }
+            Action::SetMark(name) => {
+                // Existing marks can always be moved
+                if self.marks.len() >= MAX_MARKS && !self.marks.contains_key(name) {
+                    self.last_action_rejected = true;
+                    return;
+                }
+                self.marks.insert(*name, self.focused_cell_coord());
+            }
+            Action::JumpToMark(name) => {
+                if let Some(&(row, col)) = self.marks.get(name) {
+                    self.focused_row = row;
+                    self.focused_col = col;
+                    // Mark might have been set on a bigger grid
+                    self.clamp_focus();
+                    self.on_focus_moved();
+                }
+            }
+            Action::FocusCell(row, col) => {
+                // Parsing only checks against the biggest grid
+                if self.set_focus(*row, *col).is_err() {
+                    self.last_action_rejected = true;
+                }
+            }
+            Action::NextBox | Action::PrevBox => {
+                let size = self.size();
+                let (row, col) = (self.focused_row as usize, self.focused_col as usize);
+                let box_idx = self.shape.box_index(row, col);
+                let box_idx = match action {
+                    Action::NextBox => (box_idx + 1) % size,
+                    _ => (box_idx + size - 1) % size,
+                };
+                let (row, col) = self.shape.box_cells(box_idx)[0];
+                self.focused_row = row as u8;
+                self.focused_col = col as u8;
+                self.on_focus_moved();
+            }
+            Action::FocusCenter => {
+                let center = (self.size() / 2) as u8;
+                self.focused_row = center;
+                self.focused_col = center;
+                self.on_focus_moved();
+            }
+            Action::FocusCorner(corner) => {
+                let (row, col) = corner.cell(self.size());
+                self.focused_row = row as u8;
+                self.focused_col = col as u8;
+                self.on_focus_moved();
+            }
+            Action::WriteCell(n) if self.input_mode == InputMode::Candidate => {
+                self.apply_cell_action(&Action::ToggleCandidate(*n));
+            }
+            Action::WriteCell(n) => {
+                if self.get_focused_cell().is_given {
+                    return;
+                }
+                let (row, col) = (self.focused_row as usize, self.focused_col as usize);
+                self.write_digit(row, col, *n);
+            }
+            Action::ClearCell => {
+                if self.get_focused_cell().is_given {
+                    return;
+                }
+                self.get_mut_focused_cell().digit = None;
+                self.get_mut_focused_cell().is_hint = false;
+
+                if self.options.auto_notes {
+                    // Cleared digit might now be legal again in the cell and its peers
+                    let (row, col) = (self.focused_row as usize, self.focused_col as usize);
+                    let mut cells = self.peers(row, col);
+                    cells.push((row, col));
+                    self.recompute_candidates(&cells);
+                }
+            }
+            Action::Hint => {
+                let (row, col) = (self.focused_row as usize, self.focused_col as usize);
+                let cell = self.get_focused_cell();
+                // Nothing to reveal without a known solution
+                let expected = self.expected_value(row, col);
+                if cell.is_given || cell.digit.is_some() || expected == 0 {
+                    self.last_action_rejected = true;
+                    return;
+                }
+
+                let cell = self.get_mut_focused_cell();
+                cell.digit = Some(expected);
+                cell.is_hint = true;
+                cell.clear_candidates();
+                self.prune_peer_candidates(row, col, expected);
+                if self.options.auto_notes {
+                    self.recompute_candidates(&self.peers(row, col));
+                }
+            }
+            Action::ClearMistakes => {
+                let size = self.size();
+                let mistakes: Vec<(usize, usize)> = (0..size)
+                    .flat_map(|row| (0..size).map(move |col| (row, col)))
+                    .filter(|&(row, col)| self.is_cell_wrong(row, col))
+                    .collect();
+                if mistakes.is_empty() {
+                    self.last_action_rejected = true;
+                    return;
+                }
+
+                for &(row, col) in &mistakes {
+                    self.grid[row][col].digit = None;
+                }
+                if self.options.auto_notes {
+                    let mut cells = mistakes.clone();
+                    cells.extend(mistakes.iter().flat_map(|&(row, col)| self.peers(row, col)));
+                    self.recompute_candidates(&cells);
+                }
+            }
+            Action::FillBoxSingles => {
+                let (row, col) = (self.focused_row as usize, self.focused_col as usize);
+                let (box_row, box_col) = self.shape.box_origin(row, col);
+                let singles: Vec<(usize, usize, u8)> = (box_row..box_row + self.shape.box_rows)
+                    .flat_map(|r| (box_col..box_col + self.shape.box_cols).map(move |c| (r, c)))
+                    // Givens are never empty, so never singles
+                    .filter_map(|(r, c)| Some((r, c, self.grid[r][c].single_candidate()?)))
+                    .collect();
+                if singles.is_empty() {
+                    self.last_action_rejected = true;
+                    return;
+                }
+
+                for (r, c, digit) in singles {
+                    self.write_digit(r, c, digit);
+                }
+            }
+            Action::MarkForcedCandidates => {
+                let size = self.size();
+                let forced: Vec<(usize, usize, usize)> = (0..size)
+                    .flat_map(|row| (0..size).map(move |col| (row, col)))
+                    .filter(|&(row, col)| self.grid[row][col].digit.is_none())
+                    .filter_map(|(row, col)| {
+                        let legal = self.legal_digits(row, col);
+                        let mut digits = (0..size).filter(|&n| legal[n]);
+                        match (digits.next(), digits.next()) {
+                            (Some(n), None) => Some((row, col, n)),
+                            _ => None,
+                        }
+                    })
+                    .collect();
+
+                if forced.is_empty() {
+                    self.last_action_rejected = true;
+                    return;
+                }
+
+                // Only the forced digit is left, so the player can tell it apart and commit it
+                for (row, col, n) in forced {
+                    let cell = &mut self.grid[row][col];
+                    cell.clear_candidates();
+                    cell.candidates[n] = self.pencil_color;
+                }
+            }
+            Action::FillCandidates => {
+                let size = self.size();
+                let cells: Vec<(usize, usize)> = (0..size)
+                    .flat_map(|r| (0..size).map(move |c| (r, c)))
+                    .collect();
+                self.recompute_candidates(&cells);
+            }
+            Action::AutoComplete => {
+                let digits = self.digits();
+                let solved = solver::solve_singles(&digits, self.shape)
+                    // Never fill the board with something other than the known solution
+                    .filter(|solved| {
+                        let mut rows = self.solution.iter().zip(solved);
+                        rows.all(|(expected_row, row)| {
+                            let mut pairs = expected_row.iter().zip(row);
+                            pairs.all(|(&expected, &digit)| expected == 0 || expected == digit)
+                        })
+                    });
+                let Some(solved) = solved.filter(|solved| *solved != digits) else {
+                    self.last_action_rejected = true;
+                    return;
+                };
+
+                for (row, row_vec) in solved.into_iter().enumerate() {
+                    for (col, digit) in row_vec.into_iter().enumerate() {
+                        if self.grid[row][col].digit.is_none() {
+                            self.write_digit(row, col, digit);
+                        }
+                    }
+                }
+            }
+            Action::SetCandidate(n) => {
+                if self.get_focused_cell().is_given || self.get_focused_cell().digit.is_some() {
+                    // Let the UI tell the user to clear the digit first
+                    self.last_action_rejected = true;
+                    return;
+                }
+
+                let n = *n as usize - 1;
+                self.get_mut_focused_cell().candidates[n] = self.pencil_color;
+            }
+            Action::RemoveCandidate(n) => {
+                if self.get_focused_cell().is_given || self.get_focused_cell().digit.is_some() {
+                    // Let the UI tell the user to clear the digit first
+                    self.last_action_rejected = true;
+                    return;
+                }
+                let n = *n as usize - 1;
+                self.get_mut_focused_cell().candidates[n] = 0;
+            }
+            Action::ToggleCandidate(n) => {
+                if self.get_focused_cell().is_given || self.get_focused_cell().digit.is_some() {
+                    // Let the UI tell the user to clear the digit first
+                    self.last_action_rejected = true;
+                    return;
+                }
+                let n = *n as usize - 1;
+                let pencil_color = self.pencil_color;
+                let candidate = &mut self.get_mut_focused_cell().candidates[n];
+                *candidate = if *candidate == 0 { pencil_color } else { 0 };
+            }
+            Action::ClearCandidates => {
+                self.get_mut_focused_cell().clear_candidates();
+            }
+            Action::ClearAllCandidates => {
+                // Givens never have candidates, no need to skip them
+                for cell in self.grid.iter_mut().flatten() {
+                    cell.clear_candidates();
+                }
+            }
+            Action::CycleColor => {
+                // Cycles through the palette, then back to no color
+                let cell = self.get_mut_focused_cell();
+                cell.color = (cell.color + 1) % (CELL_COLOR_COUNT + 1);
+            }
+            Action::SetColor(color) => {
+                self.get_mut_focused_cell().color = *color;
+            }
+            Action::ClearAllColors => {
+                for cell in self.grid.iter_mut().flatten() {
+                    cell.color = 0;
+                }
+            }
+            Action::ToggleFlag => {
+                // Givens can't be uncertain
+                if !self.get_focused_cell().is_given {
+                    let cell = self.get_mut_focused_cell();
+                    cell.flagged = !cell.flagged;
+                }
+            }
+            Action::HighlightCurrentDigit => {
+                self.highlighted_digit = self.get_focused_cell().digit;
+            }
+            Action::HighlightDigit(n) => {
+                self.highlighted_digit = Some(*n);
+            }
+            Action::SetPencilColor(color) => {
+                self.pencil_color = *color;
+            }
+            Action::ClearHighlight => {
+                self.highlighted_digit = None;
+            }
+            Action::SetErrorMode(mode) => {
+                self.set_error_mode(*mode);
+            }
+            Action::ToggleErrors => {
+                self.set_show_errors(self.options.error_mode == ErrorMode::Off);
+            }
+            Action::ClearAll => {
+                // Only UI state, board data and the focused cell are left untouched.
+                // The rejected action flag was already reset above.
+                self.highlighted_digit = None;
+                self.pending_overwrite = None;
+            }
+            Action::ToggleInputMode => {
+                self.input_mode = match self.input_mode {
+                    InputMode::Normal => InputMode::Candidate,
+                    InputMode::Candidate => InputMode::Normal,
+                };
+            }
+            Action::ToggleAutoNotes => {
+                self.options.auto_notes = !self.options.auto_notes;
+                if self.options.auto_notes {
+                    // Start from an up to date board
+                    let size = self.size();
+                    let cells: Vec<(usize, usize)> = (0..size)
+                        .flat_map(|r| (0..size).map(move |c| (r, c)))
+                        .collect();
+                    self.recompute_candidates(&cells);
+                }
+            }
+            Action::Undo
+            | Action::Redo
+            | Action::NewGame
+            | Action::Restart
+            | Action::ClearBoard => {
+                unreachable!("Handled by apply_action: {:?}", action)
+            }
+        }
+    }
+
+    /// Registers the `keydown` and `blur` event listeners feeding keys to the state on the
+    /// element with the specified id
+    fn listen_to(&self, element_id: &str) -> Result<(), ListenerError> {
+        let window = web_sys::window().ok_or(ListenerError::Window)?;
+        let document = window.document().ok_or(ListenerError::Document)?;
+        let element = document
+            .get_element_by_id(element_id)
+            .ok_or_else(|| ListenerError::Element(element_id.to_owned()))?;
+        self.register_listeners(&element)
+    }
+
+    /// Registers the `keydown` and `blur` event listeners feeding keys to the state on `element`
+    fn register_listeners(&self, element: &web_sys::Element) -> Result<(), ListenerError> {
+        let last_key_mtx = self.last_key.clone();
+
+        let kb_callback;
+        {
+            let last_key_mtx = last_key_mtx.clone();
+            let kb_manager = self.kb_manager.clone();
+            let ctrl_passthrough = self.ctrl_passthrough.clone();
+            let use_key_codes = self.use_key_codes.clone();
+
+            kb_callback = Closure::wrap(Box::new(move |e: KeyboardEvent| {
+                // Same key for the Ctrl combo check and the keybind lookup
+                let key = event_key(&e, *use_key_codes.borrow());
+                loop {
+                    if e.ctrl_key() {
+                        // Block event if a keybind is registered with same key, unless the
+                        // integrator left that combo to the browser
+                        if captures_ctrl_combo(
+                            &kb_manager.borrow(),
+                            &ctrl_passthrough.borrow(),
+                            key,
+                        ) {
+                            e.prevent_default();
+                        }
+                    }
+
+                    // Loop until we can obtain a mutable borrow
+                    if let Ok(mut x) = last_key_mtx.try_borrow_mut() {
+                        // If key event is for pressing down on a modifier key, ignore
+                        // (as in: ignore when user presses down on Shift itself, as shift cannot
+                        // be binded by itself)
+                        if key.is_modifier() {
+                            break;
+                        }
+
+                        let modifier = if e.shift_key() {
+                            Some(Key::Shift)
+                        } else if e.ctrl_key() {
+                            Some(Key::Control)
+                        } else if e.alt_key() {
+                            Some(Key::Alt)
+                        } else if e.meta_key() {
+                            Some(Key::Meta)
+                        } else {
+                            None
+                        };
+
+                        *x = Some(Keybind { key, modifier });
+                        console::debug_1(&format!("{:?} (k:{},c:{})", x, e.key(), e.code()).into());
+                        break;
+                    }
+                }
+            }) as Box<dyn FnMut(_)>);
+        }
+
+        // Clear any pending key when the canvas loses focus, otherwise a key pressed right
+        // before tabbing away would get applied on the next update
+        let blur_callback;
+        {
+            let last_key_mtx = last_key_mtx.clone();
+
+            blur_callback = Closure::wrap(Box::new(move || loop {
+                if let Ok(mut x) = last_key_mtx.try_borrow_mut() {
+                    *x = None;
+                    break;
+                }
+            }) as Box<dyn FnMut()>);
+        }
+
+        // Register callbacks on "keydown" and "blur" events on the element
+        let registered = element
+            .add_event_listener_with_callback("keydown", kb_callback.as_ref().unchecked_ref())
+            .and_then(|_| {
+                element.add_event_listener_with_callback(
+                    "blur",
+                    blur_callback.as_ref().unchecked_ref(),
+                )
+            });
+
+        // Callback closures need to outlive this method call, even if only one of them got
+        // registered. "Forget" the objects so that rust doesn't destroy them
+        kb_callback.forget();
+        blur_callback.forget();
+
+        registered.map_err(|e| {
+            ListenerError::Registration(e.as_string().unwrap_or_else(|| format!("{e:?}")))
+        })
+    }
+}
+
+// Methods exported to JS
+#[wasm_bindgen]
+impl GameState {
+    /// Creates a new `GameState` object and registers a `keydown` event listener on the
+    /// `#canvas` element. Fails with a message explaining what went wrong if there is no such
+    /// element or the listener can't be registered on it.
+    pub fn with_keybind_manager(kb_manager: KeybindManager) -> Result<GameState, ListenerError> {
+        let state = Self::new(kb_manager);
+        state.listen_to("canvas")?;
+        Ok(state)
+    }
+
+    /// Creates a fully set up `GameState` from a single JSON config of the form
+    /// `{ "keybinds": "...", "puzzle": "...", "options": {...}, "canvas_id": "..." }`.
+    /// Every field is optional: no keybinds, an empty board, default options and the element
+    /// with id `canvas` are used for missing ones.
+    pub fn from_config_json(json: &str) -> Result<GameState, GameConfigError> {
+        let (state, canvas_id) = Self::from_config(json)?;
+        state.listen_to(&canvas_id)?;
+        Ok(state)
+    }
+
+    /// Replaces the keybinds, e.g. when the player picks another scheme from the settings.
+    /// Takes effect for the next key press.
+    pub fn set_keybind_manager(&mut self, kb_manager: KeybindManager) {
+        *self.kb_manager.borrow_mut() = kb_manager;
+    }
+
+    /// Moves the focus to a cell, e.g. to point at a cell in a tutorial. Errors if the cell is
+    /// outside of the grid, in which case the focus doesn't move.
+    pub fn set_focus(&mut self, row: u8, col: u8) -> Result<(), CellOutOfRange> {
+        let size = self.size() as u8;
+        if row >= size || col >= size {
+            return Err(CellOutOfRange(row, col));
+        }
+        self.focused_row = row;
+        self.focused_col = col;
+        self.on_focus_moved();
+        Ok(())
+    }
+
+    /// Sets whether keys are read from `KeyboardEvent.code` rather than `KeyboardEvent.key`.
+    /// Codes name physical keys, so numpad digits can be bound separately (e.g. `Numpad1`),
+    /// at the cost of letters following the QWERTY layout. Keys without a known code still use
+    /// `KeyboardEvent.key`.
+    pub fn set_use_key_codes(&mut self, value: bool) {
+        *self.use_key_codes.borrow_mut() = value;
+    }
+
+    /// Sets whether Ctrl+`key` is left to the browser even when bound. Ctrl+C, Ctrl+V and
+    /// Ctrl+X are by default, so binding them doesn't break copy and paste. `key` uses the
+    /// keybind config notation.
+    pub fn set_ctrl_passthrough(
+        &mut self,
+        key: String,
+        passthrough: bool,
+    ) -> Result<(), KeyParseError> {
+        let key = Key::try_from_config(key)?;
+        let mut keys = self.ctrl_passthrough.borrow_mut();
+        if passthrough {
+            keys.insert(key);
+        } else {
+            keys.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Updates the game state based on the user's inputs
+    pub fn update(&mut self) {
+        if let Some(keybind) = self.consume_last_key() {
+            let actions = self
+                .kb_manager
+                .borrow()
+                .get_actions(&keybind)
+                .map(<[_]>::to_vec);
+            // Applied in order, skipping the disallowed ones
+            if actions.is_some() {
+                self.start_timer_at(now_ms());
+            }
+            for action in actions.unwrap_or_default() {
+                if !self.is_action_allowed(action.kind()) {
+                    continue;
+                }
+                self.apply_action(&action);
+                self.record_history(keybind, action);
+            }
+        }
+
+        if !self.won && self.is_complete() {
+            // Count the time up to the winning move
+            self.tick_timer_at(now_ms());
+            self.won = true;
+            self.timer_start_ms = None;
+        }
+    }
+
+    /// Number of times a wrong digit was written since the puzzle was loaded. Unlike the
+    /// mistakes shown on the board, fixing or undoing a wrong digit doesn't lower it. Only
+    /// counted while the solution is known.
+    pub fn mistakes(&self) -> u32 {
+        self.mistakes
+    }
+
+    pub fn reset_mistakes(&mut self) {
+        self.mistakes = 0;
+    }
+
+    /// Starts the timer if it isn't running yet. The timer also starts on the first key press.
+    pub fn start_timer(&mut self) {
+        self.start_timer_at(now_ms());
+    }
+
+    /// Adds the time elapsed since the last tick to `elapsed_ms`. Should be called every frame
+    /// while the timer is shown. Does nothing once the puzzle is solved.
+    pub fn tick_timer(&mut self) {
+        self.tick_timer_at(now_ms());
+    }
+
+    /// Time spent solving the current puzzle, as of the last `tick_timer` call
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed_ms
+    }
+
+    /// Returns true once the board has been correctly filled. Stays true if cells are edited
+    /// afterwards, until another puzzle is loaded.
+    pub fn has_won(&self) -> bool {
+        self.won
+    }
+
+    /// Starts a new 9x9 game, replacing the board and resetting the focus, highlight, undo
+    /// history, mistakes and timer. The keybinds and event listeners are kept, so this is the
+    /// way to start every puzzle after the first one, rather than creating another `GameState`
+    /// whose listeners would never be removed. Returns the number of givens, see
+    /// `generate_variant_grid`.
+    pub fn new_game(&mut self, seed: String, given_count: usize) -> Result<usize, GenerationError> {
+        let actual_count = self.generate_grid(seed, given_count)?;
+        self.focused_row = 0;
+        self.focused_col = 0;
+        self.highlighted_digit = None;
+        self.pending_overwrite = None;
+        Ok(actual_count)
+    }
+
+    pub fn generate_grid(
+        &mut self,
+        seed: String,
+        given_count: usize,
+    ) -> Result<usize, GenerationError> {
+        self.generate_variant_grid(seed, given_count, GridVariant::Classic)
+    }
+
+    /// Generates a new grid of the specified variant, replacing the current one.
+    /// Returns the number of givens of the new grid, which is above `given_count` when the
+    /// puzzle couldn't be masked down to it without losing its unique solution.
+    /// The undo history is cleared, unlike with the `NewGame` action.
+    pub fn generate_variant_grid(
+        &mut self,
+        seed: String,
+        given_count: usize,
+        variant: GridVariant,
+    ) -> Result<usize, GenerationError> {
+        let actual_count = self.generate_board(seed.clone(), given_count, variant)?;
+        self.undo_stack.clear();
+        self.dirty = true;
+        self.reset_progress();
+        self.generation_params = Some(GenerationParams {
+            seed,
+            given_count,
+            variant,
+            games_started: 0,
+        });
+        Ok(actual_count)
+    }
+
+    /// All actions are allowed unless restricted with `set_allowed_actions` or
+    /// `set_action_allowed`
+    pub fn is_action_allowed(&self, kind: ActionKind) -> bool {
+        !self.disallowed_actions.contains(&kind)
+    }
+
+    /// Same as `set_allowed_actions` for JS, which sees the kinds as their index in
+    /// `ActionKind`. Unknown indices are ignored.
+    pub fn set_allowed_action_kinds(&mut self, kinds: &[u8]) {
+        let kinds: Vec<ActionKind> = kinds
+            .iter()
+            .filter_map(|&i| ActionKind::ALL.get(i as usize).copied())
+            .collect();
+        self.set_allowed_actions(&kinds);
+    }
+
+    pub fn set_action_allowed(&mut self, kind: ActionKind, allowed: bool) {
+        if allowed {
+            self.disallowed_actions.remove(&kind);
+        } else {
+            self.disallowed_actions.insert(kind);
+        }
+    }
+
+    pub fn allow_all_actions(&mut self) {
+        self.disallowed_actions.clear();
+    }
+
+    /// Returns the color used for newly marked candidates, from 1 to `PENCIL_COLOR_COUNT`
+    pub fn pencil_color(&self) -> u8 {
+        self.pencil_color
+    }
+
+    /// Returns the number of actions that changed the board since the puzzle was loaded. Undo
+    /// and redo aren't counted.
+    pub fn moves(&self) -> u32 {
+        self.moves
+    }
+
+    /// Returns true if the board changed since the last call to `mark_saved`, i.e. if it needs
+    /// to be saved again. Lets JS only save when needed instead of every frame.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn practice_mode(&self) -> bool {
+        self.practice_mode
+    }
+
+    pub fn set_practice_mode(&mut self, value: bool) {
+        self.practice_mode = value;
+    }
+
+    /// Removes written digits from the candidates of their row, column and box
+    pub fn set_auto_prune_candidates(&mut self, value: bool) {
+        self.options.auto_prune_candidates = value;
+    }
+
+    /// Shows the solution faintly behind empty cells. Only has an effect in practice mode.
+    pub fn set_ghost_solution(&mut self, value: bool) {
+        self.options.ghost_solution = value;
+    }
+
+    /// Makes the givens of generated puzzles symmetric, e.g. `Central` for the classic look of
+    /// printed puzzles
+    pub fn set_symmetry(&mut self, symmetry: Symmetry) {
+        self.options.symmetry = symmetry;
+    }
+
+    /// Keeps at least `value` givens in every box of generated puzzles, so no box starts empty
+    pub fn set_min_clues_per_box(&mut self, value: u8) {
+        self.options.min_clues_per_box = value;
+    }
+
+    pub fn set_error_mode(&mut self, mode: ErrorMode) {
+        self.options.error_mode = mode;
+        if mode != ErrorMode::Off {
+            self.shown_error_mode = mode;
+        }
+    }
+
+    /// Shows mistakes with the last error mode other than `Off`, or hides them. Only changes
+    /// what is drawn, mistakes are still counted and tracked.
+    pub fn set_show_errors(&mut self, value: bool) {
+        self.options.error_mode = if value {
+            self.shown_error_mode
+        } else {
+            ErrorMode::Off
+        };
+    }
+
+    /// Makes the highlighted digit follow the digit of the focused cell as it moves
+    pub fn set_highlight_follows_focus(&mut self, value: bool) {
+        self.options.highlight_follows_focus = value;
+    }
+
+    pub fn set_wrap_skip_moves(&mut self, value: bool) {
+        self.options.wrap_skip_moves = value;
+    }
+
+    /// Allows generating puzzles with fewer givens than needed for a unique solution. The
+    /// expected values used to show errors are then those of one of the possible solutions.
+    pub fn set_allow_non_unique(&mut self, value: bool) {
+        self.options.allow_non_unique = value;
+    }
+
+    pub fn set_clear_completed_highlight(&mut self, value: bool) {
+        self.options.clear_completed_highlight = value;
+    }
+
+    /// Sets a cell as a given with the specified digit, replacing anything it contained. A
+    /// digit of 0 turns the cell back into a regular empty cell. Out of range coordinates and
+    /// digits are ignored.
+    ///
+    /// With the `editor_confirm_overwrite` option, replacing the digit of a cell by another
+    /// digit or by 0 waits for `confirm_pending` instead, see `pending_overwrite`.
+    ///
+    /// The stored solution isn't updated, see `recompute_solution`.
+    pub fn set_given(&mut self, row: usize, col: usize, digit: u8) {
+        if row >= self.size() || col >= self.size() || digit as usize > self.size() {
+            return;
+        }
+
+        let current = self.grid[row][col].digit;
+        if self.options.editor_confirm_overwrite && current.is_some_and(|n| n != digit) {
+            self.pending_overwrite = Some((row as u8, col as u8, digit));
+            return;
+        }
+        self.write_given(row, col, digit);
+    }
+
+    /// Returns the `set_given` call waiting for confirmation as `[row, col, digit]`, if any
+    pub fn pending_overwrite(&self) -> Option<Vec<u8>> {
+        self.pending_overwrite
+            .map(|(row, col, digit)| vec![row, col, digit])
+    }
+
+    /// Applies the `set_given` call waiting for confirmation. Returns false if there was none.
+    pub fn confirm_pending(&mut self) -> bool {
+        match self.pending_overwrite.take() {
+            Some((row, col, digit)) => {
+                self.write_given(row as usize, col as usize, digit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops the `set_given` call waiting for confirmation, leaving the cell as is
+    pub fn cancel_pending(&mut self) {
+        self.pending_overwrite = None;
+    }
+
+    pub fn set_editor_confirm_overwrite(&mut self, value: bool) {
+        self.options.editor_confirm_overwrite = value;
+        if !value {
+            self.pending_overwrite = None;
+        }
+    }
+
+    /// Solves the grid formed by the givens and stores it as the solution. Errors if the givens
+    /// have no solution or multiple solutions, in which case the stored solution is unchanged.
+    pub fn recompute_solution(&mut self) -> Result<(), GenerationError> {
+        let givens = self.givens();
+
+        self.solution = generation::unique_solution(&givens, self.shape)?;
+        Ok(())
+    }
+
+    /// Solves the board as currently filled, givens and player digits alike, returning the
+    /// solution in row-major order. Returns nothing if the board can't be solved, e.g. because
+    /// of a wrong digit.
+    pub fn solution_for_current(&self) -> Option<Vec<u8>> {
+        let digits = self.digits();
+        let solution = generation::solve(&digits)?;
+        Some(generation::board_to_flat(&solution, self.size()))
+    }
+
+    /// Returns up to `cap` solutions of the givens, one after the other, each in row-major
+    /// order. Lets a puzzle editor show the different solutions of a puzzle that isn't unique.
+    pub fn solutions_flat(&self, cap: usize) -> Vec<u8> {
+        generation::solve_all(&self.givens(), cap)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect()
+    }
+
+    /// Replaces the board with a 9x9 puzzle in the 81 character format read by
+    /// `format::parse_81`, e.g. pasted from another sudoku app, and stores its solution. The board
+    /// is left untouched if the puzzle is invalid or doesn't have exactly one solution, since
+    /// errors can't be shown against a solution that isn't the only one.
+    pub fn load_puzzle(&mut self, puzzle: &str) -> Result<(), LoadPuzzleError> {
+        let givens = format::parse_81(puzzle)?;
+        let solution = generation::unique_solution(&givens, GridShape::CLASSIC)?;
+        self.set_classic_board(&givens, solution);
+        Ok(())
+    }
+
+    /// Returns the givens of the current 9x9 puzzle in the 81 character format, see
+    /// `load_puzzle`. Errors for other grid sizes.
+    pub fn puzzle_string(&self) -> Result<String, PuzzleFormatError> {
+        format::to_81(&self.givens())
+    }
+
+    /// Serializes the game in progress to JSON, with the candidates and colors of every cell, so
+    /// it can be restored by `import_state`. The solution is only included if
+    /// `include_solution` is set, otherwise it's solved again on import.
+    pub fn export_state(&self, include_solution: bool) -> String {
+        let saved = SavedState {
+            size: self.size(),
+            grid: self.grid.clone(),
+            focused: (self.focused_row, self.focused_col),
+            highlighted_digit: self.highlighted_digit,
+            error_mode: self.options.error_mode,
+            solution: include_solution
+                .then(|| generation::board_to_grid(&self.solution, self.size())),
+        };
+        serde_json::to_string(&saved).unwrap()
+    }
+
+    /// Restores a game serialized by `export_state`. The board is left untouched if the JSON is
+    /// invalid, or if it has no solution and its givens can't be solved.
+    pub fn import_state(&mut self, json: &str) -> Result<(), ImportStateError> {
+        let saved: SavedState = serde_json::from_str(json)?;
+        let shape = GridShape::from_size(saved.size)
+            .filter(|_| saved.is_valid())
+            .ok_or(ImportStateError::Shape)?;
+
+        let solution = match saved.solution {
+            Some(solution) => generation::board_from_grid(&solution),
+            None => {
+                let givens: Vec<Vec<u8>> = saved
+                    .grid
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|cell| cell.digit.filter(|_| cell.is_given).unwrap_or(0))
+                            .collect()
+                    })
+                    .collect();
+                generation::solve(&givens).ok_or(ImportStateError::NoSolution)?
+            }
+        };
+
+        self.undo_stack.clear();
+        self.dirty = false;
+        self.reset_progress();
+        // Marks and edits waiting for confirmation belong to the board being replaced
+        self.marks.clear();
+        self.pending_overwrite = None;
+        self.shape = shape;
+        self.grid = saved.grid;
+        self.solution = solution;
+        (self.focused_row, self.focused_col) = saved.focused;
+        self.highlighted_digit = saved.highlighted_digit;
+        self.set_error_mode(saved.error_mode);
+        Ok(())
+    }
+
+    /// Replaces the board with a 9x9 puzzle encoded by `Puzzle::to_bytes`, e.g. from a puzzle
+    /// bank shipped with the app. The board is left untouched if the bytes are invalid.
+    pub fn load_from_bytes(&mut self, bytes: &[u8]) -> Result<(), PuzzleDecodeError> {
+        let puzzle = Puzzle::from_bytes(bytes)?;
+        let solution = generation::board_from_grid(&puzzle.solution);
+        self.set_classic_board(&puzzle.givens, solution);
+        Ok(())
+    }
+
+    /// Encodes the givens and solution of the current 9x9 puzzle with `Puzzle::to_bytes`, to
+    /// build a puzzle bank. `difficulty` is stored as is. Returns nothing for other grid sizes,
+    /// which the format can't hold.
+    pub fn puzzle_bytes(&self, difficulty: u8) -> Option<Vec<u8>> {
+        if self.shape != GridShape::CLASSIC {
+            return None;
+        }
+        let puzzle = Puzzle {
+            givens: self.givens(),
+            solution: generation::board_to_grid(&self.solution, self.size()),
+            difficulty,
+        };
+        Some(puzzle.to_bytes())
+    }
+
+    /// Solves the puzzle formed by the givens with logical techniques, reporting how many times
+    /// each technique was needed
+    pub fn difficulty_report(&self) -> DifficultyReport {
+        let givens = self.givens();
+        solver::rate_difficulty(&givens, self.shape)
+    }
+
+    /// Describes the next deduction that can be made from the digits on the board, for a "how
+    /// would you solve this" hint. Candidates are worked out from the digits, narrowed down by
+    /// the pencil marks of the cells having some so eliminations already made by the player
+    /// aren't suggested again. Returns nothing if no known technique applies.
+    pub fn next_logical_step(&self) -> Option<String> {
+        let digits = self.digits();
+        let mut candidates = solver::initial_candidates(&digits, self.shape);
+        for (cell, cell_candidates) in self
+            .grid
+            .iter()
+            .flatten()
+            .zip(candidates.iter_mut().flatten())
+        {
+            if cell.candidates.iter().any(|&c| c != 0) {
+                for (candidate, &mark) in cell_candidates.iter_mut().zip(&cell.candidates) {
+                    *candidate &= mark != 0;
+                }
+            }
+        }
+        solver::step(&digits, &candidates, self.shape).map(|step| step.to_string())
+    }
+
+    /// Checks that the stored solution is valid, matches the givens and is their only solution.
+    /// Meant to catch generator regressions while debugging.
+    pub fn verify_puzzle(&self) -> Result<(), VerifyError> {
+        let solution = generation::board_to_grid(&self.solution, self.size());
+        generation::verify_puzzle(&self.givens(), &solution)
+    }
+
+    /// Turns every digit on the board into a given and solves the resulting puzzle, for
+    /// puzzles entered by typing their digits. Errors if the puzzle doesn't have a unique
+    /// solution, in which case nothing is locked.
+    pub fn lock_givens(&mut self) -> Result<(), GenerationError> {
+        let digits = self.digits();
+        self.solution = generation::unique_solution(&digits, self.shape)?;
+
+        // Undoing a change to a given isn't allowed
+        self.undo_stack.clear();
+        self.dirty = true;
+        self.reset_progress();
+        for cell in self.grid.iter_mut().flatten() {
+            if cell.digit.is_some() {
+                cell.is_given = true;
+                cell.clear_candidates();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the completed boxes, numbered in reading order starting at 0.
+    /// A box is completed when all of its cells are filled with distinct digits, without
+    /// comparing against the solution (a box can be completed with the wrong digits, as long as
+    /// they don't conflict with eachother).
+    pub fn completed_boxes(&self) -> Vec<usize> {
+        let shape = self.shape;
+        let boxes_per_row = shape.size() / shape.box_cols;
+
+        (0..shape.size())
+            .filter(|&box_idx| {
+                let row_offset = box_idx / boxes_per_row * shape.box_rows;
+                let col_offset = box_idx % boxes_per_row * shape.box_cols;
+
+                let mut seen = [false; 9];
+                for row in row_offset..row_offset + shape.box_rows {
+                    for col in col_offset..col_offset + shape.box_cols {
+                        match self.grid[row][col].digit {
+                            Some(n) if !seen[n as usize - 1] => seen[n as usize - 1] = true,
+                            // Empty or duplicate digit
+                            _ => return false,
+                        }
+                    }
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// Fills every empty cell like the `AutoComplete` action, in one undoable step. Returns true
+    /// if the board was completed, false if it was left untouched because it can't be finished
+    /// with singles alone.
+    pub fn auto_complete(&mut self) -> bool {
+        self.apply_action(&Action::AutoComplete);
+        !self.last_action_rejected
+    }
+
+    /// Returns true if the last applied action was rejected, e.g. trying to add a candidate to
+    /// a cell that already holds a digit
+    pub fn last_action_rejected(&self) -> bool {
+        self.last_action_rejected
+    }
+
+    /// Returns true if any digit on the board conflicts with one of its peers. Stops at the
+    /// first conflict found, making it cheap enough to poll every frame.
+    pub fn board_has_contradiction(&self) -> bool {
+        let size = self.size();
+        (0..size).any(|row| (0..size).any(|col| self.has_conflict(row, col)))
+    }
+
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    /// Sets the input mode, e.g. from an on-screen pencil button. Values that aren't a valid
+    /// `InputMode` are rejected by the bindings before reaching this method.
+    pub fn set_input_mode(&mut self, mode: InputMode) {
+        self.input_mode = mode;
+    }
+
+    pub fn auto_notes(&self) -> bool {
+        self.options.auto_notes
+    }
+
+    /// Returns the input history as a JSON array of `{ timestamp_ms, keybind, action }` objects
+    pub fn history_json(&self) -> String {
+        serde_json::to_string(&self.history).unwrap()
+    }
+
+    /// Describes the focused cell in words, for screen readers, e.g. "Row 3, column 5, empty,
+    /// candidates 2 4 7" or "Row 1, column 1, 8, given"
+    pub fn describe_focused_cell(&self) -> String {
+        let (row, col) = self.focused_cell_coord();
+        let cell = self.get_focused_cell();
+        let position = format!("Row {}, column {}", row + 1, col + 1);
+
+        match cell.digit {
+            Some(digit) if cell.is_given => format!("{position}, {digit}, given"),
+            Some(digit) => format!("{position}, {digit}"),
+            None => {
+                let candidates: Vec<String> = (1..=9)
+                    .filter(|&n| cell.has_candidate(n))
+                    .map(|n| n.to_string())
+                    .collect();
+                if candidates.is_empty() {
+                    format!("{position}, empty")
+                } else {
+                    format!("{position}, empty, candidates {}", candidates.join(" "))
+                }
+            }
+        }
+    }
+
+    /// Returns `aria_grid_state` as a JSON array of strings
+    pub fn aria_grid_state_json(&self) -> String {
+        serde_json::to_string(&self.aria_grid_state()).unwrap()
+    }
+
+    /// Returns the digit of every cell in row-major order, with 0 meaning an empty cell.
+    /// Crosses the wasm boundary as a single typed array instead of one call per cell.
+    pub fn digits_flat(&self) -> Vec<u8> {
+        self.grid
+            .iter()
+            .flat_map(|r| r.iter())
+            .map(|cell| cell.digit.unwrap_or(0))
+            .collect()
+    }
+
+    /// Returns how many times each digit is placed, see `digit_counts`. Only the first `size`
+    /// entries are relevant on smaller grids.
+    pub fn digit_counts_flat(&self) -> Vec<u8> {
+        self.digit_counts().to_vec()
+    }
+
+    /// Returns 1 for each digit placed on every row of the board and 0 for the others, index `n`
+    /// being digit `n + 1`, see `completed_digits`
+    pub fn completed_digits_flat(&self) -> Vec<u8> {
+        self.completed_digits().map(u8::from).to_vec()
+    }
+
+    /// Returns the empty cells as `[row0, col0, row1, col1, ...]`, see `empty_cells`
+    pub fn empty_cells_flat(&self) -> Vec<u8> {
+        self.empty_cells()
+            .into_iter()
+            .flat_map(|(r, c)| [r, c])
+            .collect()
+    }
+
+    /// Returns the flagged cells as `[row0, col0, row1, col1, ...]`, see `flagged_cells`
+    pub fn flagged_cells_flat(&self) -> Vec<u8> {
+        self.flagged_cells()
+            .into_iter()
+            .flat_map(|(r, c)| [r as u8, c as u8])
+            .collect()
+    }
+
+    /// Returns the cells conflicting with a peer as `[row0, col0, row1, col1, ...]`, see
+    /// `conflicts`
+    pub fn conflicts_flat(&self) -> Vec<u8> {
+        self.conflicts()
+            .into_iter()
+            .flat_map(|(r, c)| [r as u8, c as u8])
+            .collect()
+    }
+
+    /// Returns the peers of a cell containing `digit` as `[row0, col0, row1, col1, ...]`, see
+    /// `conflicting_peers`
+    pub fn conflicting_peers_flat(&self, row: u8, col: u8, digit: u8) -> Vec<u8> {
+        self.conflicting_peers(row, col, digit)
+            .into_iter()
+            .flat_map(|(r, c)| [r, c])
+            .collect()
+    }
+
+    /// Returns the candidates of every cell in row-major order, as bitmasks where bit `n - 1`
+    /// is set if `n` is a candidate of the cell.
+    pub fn candidate_masks_flat(&self) -> Vec<u16> {
+        self.grid
+            .iter()
+            .flat_map(|r| r.iter())
+            .map(|cell| {
+                cell.candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &color)| color != 0)
+                    .fold(0, |mask, (n, _)| mask | (1 << n))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::UNIQUE_GRID;
+
+    fn new_state() -> GameState {
+        GameState::new(KeybindManager::with_config("").unwrap())
+    }
+
+    /// Builds a state whose givens are the non-zero digits of `grid`
+    fn state_with_givens(grid: &[[u8; 9]; 9]) -> GameState {
+        let mut state = new_state();
+        for (row, row_arr) in grid.iter().enumerate() {
+            for (col, &digit) in row_arr.iter().enumerate() {
+                state.set_given(row, col, digit);
+            }
+        }
+        state
+    }
+
+    #[test]
+    fn test_recompute_solution_unique() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        assert_eq!(state.recompute_solution(), Ok(()));
+
+        for (row, row_arr) in UNIQUE_GRID.iter().enumerate() {
+            for (col, &digit) in row_arr.iter().enumerate() {
+                let expected = state.expected_value(row, col);
+                assert!((1..=9).contains(&expected));
+                if digit != 0 {
+                    assert_eq!(expected, digit);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_recompute_solution_contradiction() {
+        let mut grid = UNIQUE_GRID;
+        // Second 1 in the first row
+        grid[0][0] = 1;
+        let mut state = state_with_givens(&grid);
+
+        assert_eq!(state.recompute_solution(), Err(GenerationError::NoSolution));
+        // Solution is left untouched
+        assert_eq!(state.expected_value(0, 0), 0);
+    }
+
+    #[test]
+    fn test_recompute_solution_multiple() {
+        let mut grid = UNIQUE_GRID;
+        // Removing givens from the unique grid, same as the generation's 5 solution fixture
+        grid[0][1] = 0;
+        grid[3][4] = 0;
+        grid[6][3] = 0;
+        grid[7][3] = 0;
+        grid[8][7] = 0;
+        let mut state = state_with_givens(&grid);
+
+        assert_eq!(
+            state.recompute_solution(),
+            Err(GenerationError::MultipleSolutions)
+        );
+    }
+
+    #[test]
+    fn test_completed_boxes() {
+        let mut state = new_state();
+        for n in 0..9 {
+            state.set_given(n / 3, n % 3, n as u8 + 1);
+        }
+        assert_eq!(state.completed_boxes(), vec![0]);
+
+        // Duplicate digit in the box
+        state.set_given(0, 0, 2);
+        assert!(state.completed_boxes().is_empty());
+    }
+
+    #[test]
+    fn test_candidate_on_filled_cell_rejected() {
+        let mut state = new_state();
+        state.apply_action(&Action::WriteCell(5));
+        assert!(!state.last_action_rejected());
+
+        state.apply_action(&Action::SetCandidate(3));
+        assert!(state.last_action_rejected());
+        assert_eq!(state.get_focused_cell().candidates, [0; 9]);
+
+        // Next action resets the flag
+        state.apply_action(&Action::MoveCol(1, true));
+        assert!(!state.last_action_rejected());
+    }
+
+    /// Builds a candidate array containing `digits`
+    fn candidates(digits: &[u8]) -> [u8; 9] {
+        let mut candidates = [0; 9];
+        for &d in digits {
+            candidates[d as usize - 1] = 1;
+        }
+        candidates
+    }
+
+    #[test]
+    fn test_write_clear_candidate_transitions() {
+        use Action::*;
+
+        // Actions applied to an empty cell, and the resulting digit, candidates and whether the
+        // last action was rejected
+        type Case = (Vec<Action>, Option<u8>, [u8; 9], bool);
+        let cases: Vec<Case> = vec![
+            // Candidates are dropped once a digit is placed, even one equal to the digit
+            (
+                vec![SetCandidate(3), WriteCell(5)],
+                Some(5),
+                candidates(&[]),
+                false,
+            ),
+            (
+                vec![SetCandidate(5), WriteCell(5)],
+                Some(5),
+                candidates(&[]),
+                false,
+            ),
+            (
+                vec![ToggleCandidate(5), WriteCell(5)],
+                Some(5),
+                candidates(&[]),
+                false,
+            ),
+            // Candidates can't be edited behind a digit
+            (
+                vec![WriteCell(5), ToggleCandidate(3)],
+                Some(5),
+                candidates(&[]),
+                true,
+            ),
+            (
+                vec![WriteCell(5), SetCandidate(5)],
+                Some(5),
+                candidates(&[]),
+                true,
+            ),
+            (
+                vec![WriteCell(5), RemoveCandidate(5)],
+                Some(5),
+                candidates(&[]),
+                true,
+            ),
+            // Clearing the digit doesn't bring back stale candidates
+            (
+                vec![SetCandidate(3), WriteCell(5), ClearCell],
+                None,
+                candidates(&[]),
+                false,
+            ),
+            // Candidates can be edited again once the digit is cleared
+            (
+                vec![WriteCell(5), ClearCell, ToggleCandidate(5)],
+                None,
+                candidates(&[5]),
+                false,
+            ),
+            (
+                vec![WriteCell(5), ClearCell, SetCandidate(3), ToggleCandidate(5)],
+                None,
+                candidates(&[3, 5]),
+                false,
+            ),
+            // Overwriting a digit keeps candidates cleared
+            (
+                vec![WriteCell(5), WriteCell(6)],
+                Some(6),
+                candidates(&[]),
+                false,
+            ),
+            (
+                vec![SetCandidate(1), ClearCandidates, WriteCell(2), ClearCell],
+                None,
+                candidates(&[]),
+                false,
+            ),
+        ];
+
+        for (actions, digit, expected_candidates, rejected) in cases {
+            let mut state = new_state();
+            for action in &actions {
+                state.apply_action(action);
+            }
+            let cell = state.get_focused_cell();
+            assert_eq!(cell.digit, digit, "{:?}", actions);
+            assert_eq!(cell.candidates, expected_candidates, "{:?}", actions);
+            assert_eq!(state.last_action_rejected(), rejected, "{:?}", actions);
+        }
+    }
+
+    #[test]
+    fn test_auto_notes() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::ToggleAutoNotes);
+        assert!(state.auto_notes());
+
+        // Cell (0, 0) sees 1, 2, 3, 4, 5, 7, 9 in its row, column and box
+        let mut expected = candidates(&[6, 8]);
+        assert_eq!(state.grid()[0][0].candidates, expected);
+
+        // Writing 6 in (0, 2) removes it from its peer (0, 0)
+        state.focused_col = 2;
+        state.apply_action(&Action::WriteCell(6));
+        expected[5] = 0;
+        assert_eq!(state.grid()[0][0].candidates, expected);
+
+        // Clearing it makes it legal again
+        state.apply_action(&Action::ClearCell);
+        expected[5] = 1;
+        assert_eq!(state.grid()[0][0].candidates, expected);
+    }
+
+    #[test]
+    fn test_auto_prune_candidates() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.set_auto_prune_candidates(true);
+        // 6 marked in (0, 0), a peer of (0, 2), and in (8, 8) which isn't
+        state.grid[0][0].candidates = candidates(&[6, 8]);
+        state.grid[8][8].candidates = candidates(&[6]);
+
+        state.focused_col = 2;
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.grid[0][0].candidates, candidates(&[8]));
+        assert_eq!(state.grid[8][8].candidates, candidates(&[6]));
+
+        // Nothing gets pruned while disabled
+        state.set_auto_prune_candidates(false);
+        state.grid[0][0].candidates = candidates(&[6, 8]);
+        state.apply_action(&Action::ClearCell);
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.grid[0][0].candidates, candidates(&[6, 8]));
+    }
+
+    #[test]
+    fn test_board_has_contradiction() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        assert!(!state.board_has_contradiction());
+
+        // 7 is already in the first column
+        state.apply_action(&Action::WriteCell(7));
+        assert!(state.board_has_contradiction());
+
+        state.apply_action(&Action::ClearCell);
+        assert!(!state.board_has_contradiction());
+    }
+
+    #[test]
+    fn test_candidate_input_mode() {
+        let mut state = new_state();
+        state.set_input_mode(InputMode::Candidate);
+        state.apply_action(&Action::WriteCell(4));
+        assert_eq!(state.get_focused_cell().digit, None);
+        assert!(state.get_focused_cell().has_candidate(4));
+
+        // Writing again toggles it back off
+        state.apply_action(&Action::WriteCell(4));
+        assert!(!state.get_focused_cell().has_candidate(4));
+
+        state.apply_action(&Action::ToggleInputMode);
+        assert_eq!(state.input_mode(), InputMode::Normal);
+        state.apply_action(&Action::WriteCell(4));
+        assert_eq!(state.get_focused_cell().digit, Some(4));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let puzzle: String = UNIQUE_GRID
+            .iter()
+            .flatten()
+            .map(|d| d.to_string())
+            .collect();
+        let json = format!(
+            r#"{{ "keybinds": "1=writecell(1)", "puzzle": "{puzzle}", "options": {{ "auto_notes": true }} }}"#
+        );
+        let (state, canvas_id) = GameState::from_config(&json).unwrap();
+        assert_eq!(canvas_id, "canvas");
+        assert!(state.auto_notes());
+        assert_eq!(state.error_mode(), ErrorMode::Both);
+        assert!(state.grid[0][1].is_given);
+        assert_eq!(state.expected_value(0, 0), 6);
+
+        // Every field is optional
+        let (state, canvas_id) = GameState::from_config(r#"{ "canvas_id": "board" }"#).unwrap();
+        assert_eq!(canvas_id, "board");
+        assert!(state.grid.iter().flatten().all(|c| c.digit.is_none()));
+    }
+
+    #[test]
+    fn test_from_config_errors() {
+        let error = |json: &str| GameState::from_config(json).err().unwrap();
+
+        assert!(matches!(error("{"), GameConfigError::Json(_)));
+        assert!(matches!(
+            error(r#"{ "keybinds": "k" }"#),
+            GameConfigError::Keybinds(_)
+        ));
+        assert!(matches!(
+            error(r#"{ "puzzle": "123" }"#),
+            GameConfigError::Puzzle(PuzzleFormatError::Length(3))
+        ));
+        let empty_puzzle = format!(r#"{{ "puzzle": "{}" }}"#, "0".repeat(81));
+        assert!(matches!(
+            error(&empty_puzzle),
+            GameConfigError::Solution(GenerationError::MultipleSolutions)
+        ));
+        assert!(matches!(
+            error(r#"{ "options": { "error_mode": "sometimes" } }"#),
+            GameConfigError::Options(_)
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_peers() {
+        let state = state_with_givens(&UNIQUE_GRID);
+
+        // (4, 1) sees a 4 in its column, a 5 in its box and a 2 in its row
+        assert_eq!(state.conflicting_peers(4, 1, 4), vec![(6, 1)]);
+        assert_eq!(state.conflicting_peers(4, 1, 5), vec![(3, 0)]);
+        assert_eq!(state.conflicting_peers(4, 1, 2), vec![(4, 6)]);
+        // 1 is both in its row and column
+        assert_eq!(state.conflicting_peers(4, 1, 1), vec![(4, 2), (0, 1)]);
+        assert!(state.conflicting_peers(4, 1, 3).is_empty());
+        assert_eq!(state.conflicting_peers_flat(4, 1, 1), vec![4, 2, 0, 1]);
+
+        // Invalid coordinates and digits
+        assert!(state.conflicting_peers(9, 0, 1).is_empty());
+        assert!(state.conflicting_peers(0, 9, 1).is_empty());
+        assert!(state.conflicting_peers(4, 1, 0).is_empty());
+        assert!(state.conflicting_peers(4, 1, 10).is_empty());
+    }
+
+    #[test]
+    fn test_lock_givens() {
+        let mut state = new_state();
+        for (row, row_arr) in UNIQUE_GRID.iter().enumerate() {
+            for (col, &digit) in row_arr.iter().enumerate() {
+                if digit != 0 {
+                    state.focused_row = row as u8;
+                    state.focused_col = col as u8;
+                    state.apply_action(&Action::WriteCell(digit));
+                }
+            }
+        }
+        assert_eq!(state.lock_givens(), Ok(()));
+        assert!(state.grid[0][1].is_given);
+        assert!(!state.grid[0][0].is_given);
+        assert_eq!(state.expected_value(0, 0), 6);
+
+        // Not enough digits for a unique solution, nothing gets locked
+        let mut state = new_state();
+        state.apply_action(&Action::WriteCell(1));
+        assert_eq!(state.lock_givens(), Err(GenerationError::MultipleSolutions));
+        assert!(!state.grid[0][0].is_given);
+    }
+
+    #[test]
+    fn test_clear_all() {
+        let mut state = new_state();
+        state.apply_action(&Action::WriteCell(5));
+        state.apply_action(&Action::HighlightCurrentDigit);
+        state.apply_action(&Action::SetCandidate(3));
+        assert!(state.last_action_rejected());
+        state.set_editor_confirm_overwrite(true);
+        state.set_given(8, 8, 1);
+        state.set_given(8, 8, 2);
+        assert!(state.pending_overwrite().is_some());
+
+        state.apply_action(&Action::ClearAll);
+        assert_eq!(state.highlighted_digit(), None);
+        assert_eq!(state.pending_overwrite(), None);
+        assert_eq!(state.grid[8][8].digit, Some(1));
+        assert!(!state.last_action_rejected());
+        assert_eq!(state.focused_cell_coord(), (0, 0));
+        assert_eq!(state.grid[0][0].digit, Some(5));
+    }
+
+    #[test]
+    fn test_puzzle_bytes() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+        let bytes = state.puzzle_bytes(2).unwrap();
+
+        let mut loaded = new_state();
+        assert_eq!(loaded.load_from_bytes(&bytes), Ok(()));
+        assert_eq!(loaded.digits_flat(), state.digits_flat());
+        assert_eq!(loaded.solution, state.solution);
+        assert!(loaded.grid[0][1].is_given);
+
+        // Invalid bytes leave the board untouched
+        assert!(loaded.load_from_bytes(&bytes[..10]).is_err());
+        assert_eq!(loaded.digits_flat(), state.digits_flat());
+
+        // Only 9x9 puzzles fit in the format
+        state
+            .generate_board("EXAMPLE_SEED".into(), 20, GridVariant::Six)
+            .unwrap();
+        assert_eq!(state.puzzle_bytes(2), None);
+    }
+
+    #[test]
+    fn test_move_skip_filled() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let right = Action::try_from("moveskip(right)".to_owned()).unwrap();
+        let down = Action::try_from("moveSkip(Down)".to_owned()).unwrap();
+
+        // Row 0 is [0, 1, 0, 0, 2, 0, 3, 0, 4]
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (0, 2));
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (0, 3));
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (0, 5));
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (0, 7));
+
+        // No empty cell left in the row, stay in place unless wrapping
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (0, 7));
+        state.set_wrap_skip_moves(true);
+        // Row 1 is [0, 0, 2, 0, 0, 5, 6, 1, 0]
+        state.apply_action(&right);
+        assert_eq!(state.focused_cell_coord(), (1, 0));
+        state.apply_action(&Action::MoveSkipFilled(Direction::Left));
+        assert_eq!(state.focused_cell_coord(), (0, 7));
+
+        // Column 8 is [4, 0, 0, 1, 0, 5, 9, 0, 0], wraps around to column 0 of the grid
+        state.focused_col = 8;
+        state.focused_row = 8;
+        state.apply_action(&down);
+        assert_eq!(state.focused_cell_coord(), (0, 0));
+    }
+
+    #[test]
+    fn test_ghost_digit() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+
+        // Requires both the option and practice mode
+        state.set_ghost_solution(true);
+        assert_eq!(state.ghost_digit(0, 0), None);
+        state.set_practice_mode(true);
+        assert_eq!(state.ghost_digit(0, 0), Some(6));
+        state.set_ghost_solution(false);
+        assert_eq!(state.ghost_digit(0, 0), None);
+
+        // Only shown in empty cells
+        state.set_ghost_solution(true);
+        assert_eq!(state.ghost_digit(0, 1), None);
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.ghost_digit(0, 0), None);
+    }
+
+    #[test]
+    fn test_allowed_actions() {
+        let mut state = GameState::new(
+            KeybindManager::with_config("1 = writeCell(1)\nl = moveCol(1, true)").unwrap(),
+        );
+        let press = |state: &mut GameState, key: &str| {
+            let key = Key::try_from_config(key.to_owned()).unwrap();
+            *state.last_key.borrow_mut() = Some(Keybind {
+                key,
+                modifier: None,
+            });
+            state.update();
+        };
+
+        state.set_allowed_actions(&[ActionKind::MoveRow, ActionKind::MoveCol]);
+        assert!(!state.is_action_allowed(ActionKind::WriteCell));
+        press(&mut state, "1");
+        assert_eq!(state.grid[0][0].digit, None);
+        assert!(state.history().is_empty());
+        press(&mut state, "l");
+        assert_eq!(state.focused_cell_coord(), (0, 1));
+
+        state.set_action_allowed(ActionKind::WriteCell, true);
+        press(&mut state, "1");
+        assert_eq!(state.grid[0][1].digit, Some(1));
+
+        state.set_allowed_actions(&[]);
+        press(&mut state, "l");
+        assert_eq!(state.focused_cell_coord(), (0, 1));
+        // As called from JS
+        state.set_allowed_action_kinds(&[ActionKind::MoveCol as u8, 200]);
+        assert!(state.is_action_allowed(ActionKind::MoveCol));
+        assert!(!state.is_action_allowed(ActionKind::MoveRow));
+        state.allow_all_actions();
+        press(&mut state, "l");
+        assert_eq!(state.focused_cell_coord(), (0, 2));
+    }
+
+    #[test]
+    fn test_undo_redo_cells() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::SetCandidate(6));
+        let with_candidate = state.grid[0][0];
+        state.apply_action(&Action::WriteCell(8));
+        let with_digit = state.grid[0][0];
+
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][0], with_candidate);
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][0], Cell::new());
+        state.apply_action(&Action::Redo);
+        state.apply_action(&Action::Redo);
+        assert_eq!(state.grid[0][0], with_digit);
+        state.apply_action(&Action::Redo);
+        assert!(state.last_action_rejected());
+
+        // Givens are never altered
+        state.focused_col = 1;
+        state.apply_action(&Action::ClearCell);
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][1].digit, Some(1));
+        assert!(state.grid[0][1].is_given);
+
+        // Only the last UNDO_CAPACITY changes can be undone
+        state.focused_col = 0;
+        for i in 0..UNDO_CAPACITY + 10 {
+            state.apply_action(&Action::WriteCell(i as u8 % 9 + 1));
+        }
+        for _ in 0..UNDO_CAPACITY {
+            state.apply_action(&Action::Undo);
+            assert!(!state.last_action_rejected());
+        }
+        state.apply_action(&Action::Undo);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_undo_new_game() {
+        let mut state = new_state();
+        state.generate_grid("EXAMPLE_SEED".into(), 30).unwrap();
+        state.apply_action(&Action::MoveSkipFilled(Direction::Right));
+        state.apply_action(&Action::WriteCell(1));
+        let digits = state.digits_flat();
+        let solution = state.solution;
+        state.mistakes = 2;
+        state.elapsed_ms = 5000.;
+        state.won = true;
+
+        state.apply_action(&Action::NewGame);
+        assert!(!state.last_action_rejected());
+        assert_ne!(state.solution, solution);
+        assert_eq!(
+            (state.moves(), state.mistakes(), state.elapsed_ms()),
+            (0, 0, 0.)
+        );
+        assert!(!state.has_won());
+
+        // A single undo brings back the previous puzzle, including the player's digits and
+        // progress
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.digits_flat(), digits);
+        assert_eq!(state.solution, solution);
+        assert_eq!(
+            (state.moves(), state.mistakes(), state.elapsed_ms()),
+            (1, 2, 5000.)
+        );
+        assert!(state.has_won());
+        state.apply_action(&Action::Undo);
+        assert_ne!(state.digits_flat(), digits);
+
+        // Redo goes back to the new game
+        state.apply_action(&Action::Redo);
+        state.apply_action(&Action::Redo);
+        assert_ne!(state.solution, solution);
+        state.apply_action(&Action::Redo);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_undo_restart_and_clear_board() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::WriteCell(6));
+        state.apply_action(&Action::MoveCol(2, true));
+        state.apply_action(&Action::SetCandidate(5));
+        let digits = state.digits_flat();
+        let candidates = state.candidate_masks_flat();
+
+        state.apply_action(&Action::Restart);
+        assert_eq!(state.grid[0][0].digit, None);
+        assert_eq!(state.grid[0][2].candidates, [0; 9]);
+        assert_eq!(state.grid[0][1].digit, Some(1));
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.digits_flat(), digits);
+        assert_eq!(state.candidate_masks_flat(), candidates);
+
+        state.apply_action(&Action::ClearBoard);
+        assert!(state.digits_flat().iter().all(|&d| d == 0));
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.digits_flat(), digits);
+
+        // A new action drops the undone changes
+        state.apply_action(&Action::Undo);
+        state.apply_action(&Action::WriteCell(3));
+        state.apply_action(&Action::Redo);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_new_game_without_generation() {
+        let mut state = new_state();
+        state.apply_action(&Action::NewGame);
+        assert!(state.last_action_rejected());
+        state.apply_action(&Action::Undo);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_describe_focused_cell() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        assert_eq!(state.describe_focused_cell(), "Row 1, column 1, empty");
+
+        state.apply_action(&Action::SetCandidate(8));
+        state.apply_action(&Action::SetCandidate(6));
+        assert_eq!(
+            state.describe_focused_cell(),
+            "Row 1, column 1, empty, candidates 6 8"
+        );
+
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.describe_focused_cell(), "Row 1, column 1, 6");
+
+        state.apply_action(&Action::MoveCol(1, true));
+        assert_eq!(state.describe_focused_cell(), "Row 1, column 2, 1, given");
+    }
+
+    #[test]
+    fn test_error_modes() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+        // Solution of (0, 0) is 6, 8 doesn't conflict with any peer
+        state.apply_action(&Action::WriteCell(8));
+        // Solution of (0, 2) is 5, 1 conflicts with the given (0, 1)
+        state.apply_action(&Action::MoveCol(2, true));
+        state.apply_action(&Action::WriteCell(1));
+        assert_eq!(state.conflicts(), vec![(0, 1), (0, 2), (4, 2)]);
+
+        let errors = |state: &GameState| {
+            [(0, 0), (0, 1), (0, 2)].map(|(row, col)| state.cell_error(row, col))
+        };
+        let (conflict, mismatch) = (Some(CellError::Conflict), Some(CellError::Mismatch));
+
+        assert_eq!(state.error_mode(), ErrorMode::Both);
+        assert_eq!(errors(&state), [mismatch, conflict, mismatch]);
+
+        let action = Action::try_from("setErrorMode(RuleConflicts)".to_owned()).unwrap();
+        state.apply_action(&action);
+        assert_eq!(errors(&state), [None, conflict, conflict]);
+
+        state.set_error_mode(ErrorMode::VsSolution);
+        assert_eq!(errors(&state), [mismatch, None, mismatch]);
+
+        state.set_error_mode(ErrorMode::Off);
+        assert_eq!(errors(&state), [None, None, None]);
+        // Conflicts can still be queried
+        assert_eq!(state.conflicts().len(), 3);
+    }
+
+    #[test]
+    fn test_difficulty_report() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let report = state.difficulty_report();
+        assert!(report.steps > 0);
+        assert_eq!(
+            report.steps,
+            report.naked_singles + report.hidden_singles + report.pointing_pairs
+        );
+
+        // Player digits aren't part of the puzzle
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.difficulty_report(), report);
+    }
+
+    #[test]
+    fn test_toggle_flag() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::WriteCell(6));
+        state.apply_action(&Action::ToggleFlag);
+        state.apply_action(&Action::MoveRow(1, true));
+        state.apply_action(&Action::ToggleFlag);
+        assert_eq!(state.flagged_cells(), vec![(0, 0), (1, 0)]);
+        assert_eq!(state.flagged_cells_flat(), vec![0, 0, 1, 0]);
+
+        // Flags stay when the digit changes, and can be undone
+        state.apply_action(&Action::WriteCell(3));
+        assert!(state.grid[1][0].flagged);
+        state.apply_action(&Action::ToggleFlag);
+        assert_eq!(state.flagged_cells(), vec![(0, 0)]);
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.flagged_cells(), vec![(0, 0), (1, 0)]);
+
+        // Givens can't be flagged
+        state.apply_action(&Action::MoveRow(1, true));
+        state.apply_action(&Action::ToggleFlag);
+        assert!(!state.grid[2][0].flagged);
+    }
+
+    #[test]
+    fn test_highlight_follows_focus() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::HighlightDigit(5));
+        state.apply_action(&Action::MoveCol(1, true));
+        assert_eq!(state.highlighted_digit(), Some(5));
+
+        state.set_highlight_follows_focus(true);
+        state.apply_action(&Action::MoveCol(1, true));
+        assert_eq!(state.highlighted_digit(), None);
+        state.apply_action(&Action::MoveCol(-1, true));
+        assert_eq!(state.highlighted_digit(), Some(1));
+        state.apply_action(&Action::MoveRow(2, true));
+        assert_eq!(state.highlighted_digit(), None);
+    }
+
+    #[test]
+    fn test_empty_cells() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let empty_count = UNIQUE_GRID.iter().flatten().filter(|&&d| d == 0).count();
+        assert_eq!(state.empty_cells().len(), empty_count);
+        assert_eq!(state.empty_cells()[..2], [(0, 0), (0, 2)]);
+
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.empty_cells().len(), empty_count - 1);
+        assert_eq!(state.empty_cells_flat()[..2], [0, 2]);
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut state = new_state();
+        let parse = |s: &str| Action::try_from(s.to_owned()).unwrap();
+
+        state.apply_action(&Action::MoveRow(3, true));
+        state.apply_action(&Action::MoveCol(4, true));
+        state.apply_action(&parse("setmark(a)"));
+        state.apply_action(&Action::MoveRow(5, true));
+        state.apply_action(&parse("jumpmark(a)"));
+        assert_eq!(state.focused_cell_coord(), (3, 4));
+
+        // Unset marks do nothing
+        state.apply_action(&parse("jumpMark(b)"));
+        assert_eq!(state.focused_cell_coord(), (3, 4));
+
+        // Up to 10 marks, existing ones can still be moved
+        for name in "bcdefghij".chars() {
+            state.apply_action(&Action::SetMark(name));
+        }
+        state.apply_action(&Action::SetMark('k'));
+        assert!(state.last_action_rejected());
+        state.apply_action(&Action::MoveRow(-3, true));
+        state.apply_action(&Action::SetMark('a'));
+        assert!(!state.last_action_rejected());
+        state.apply_action(&Action::MoveRow(1, true));
+        state.apply_action(&Action::JumpToMark('a'));
+        assert_eq!(state.focused_cell_coord(), (0, 4));
+
+        assert!(Action::try_from("setmark(ab)".to_owned()).is_err());
+        assert!(Action::try_from("setmark(-)".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_dirty_flag() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        assert!(state.is_dirty());
+        state.mark_saved();
+
+        // Moving and highlighting don't change the board
+        state.apply_action(&Action::MoveCol(2, true));
+        state.apply_action(&Action::HighlightDigit(3));
+        assert!(!state.is_dirty());
+
+        state.apply_action(&Action::SetCandidate(3));
+        assert!(state.is_dirty());
+        state.mark_saved();
+
+        state.apply_action(&Action::Undo);
+        assert!(state.is_dirty());
+        state.mark_saved();
+        // Nothing left to undo
+        state.apply_action(&Action::Undo);
+        assert!(!state.is_dirty());
+
+        state.apply_action(&Action::Restart);
+        assert!(state.is_dirty());
+    }
+
+    #[test]
+    fn test_pencil_colors() {
+        let mut state = new_state();
+        state.apply_action(&Action::SetCandidate(1));
+        state.apply_action(&Action::SetPencilColor(3));
+        state.apply_action(&Action::SetCandidate(2));
+        state.apply_action(&Action::ToggleCandidate(5));
+        assert_eq!(state.get_focused_cell().candidates[..5], [1, 3, 0, 0, 3]);
+
+        // Toggling a marked candidate unmarks it, whatever its color
+        state.apply_action(&Action::ToggleCandidate(1));
+        assert!(!state.get_focused_cell().has_candidate(1));
+        assert_eq!(state.candidate_masks_flat()[0], 0b10010);
+
+        // Auto notes keep the color of candidates already marked
+        state.apply_action(&Action::SetPencilColor(2));
+        state.apply_action(&Action::ToggleAutoNotes);
+        assert_eq!(
+            state.get_focused_cell().candidates,
+            [2, 3, 2, 2, 3, 2, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn test_set_keybind_manager() {
+        let mut state = GameState::new(KeybindManager::with_config("1 = writeCell(1)").unwrap());
+        let press = |state: &mut GameState| {
+            *state.last_key.borrow_mut() = Some(Keybind {
+                key: Key::try_from_config("1".to_owned()).unwrap(),
+                modifier: None,
+            });
+            state.update();
+        };
+
+        press(&mut state);
+        assert_eq!(state.grid[0][0].digit, Some(1));
+
+        state.set_keybind_manager(KeybindManager::with_config("1 = writeCell(2)").unwrap());
+        press(&mut state);
+        assert_eq!(state.grid[0][0].digit, Some(2));
+    }
+
+    #[test]
+    fn test_ctrl_passthrough() {
+        let mut state = GameState::new(
+            KeybindManager::with_config("C-c = clearCell()\nC-z = undo()\nC-F5 = restart()")
+                .unwrap(),
+        );
+        let captures = |state: &GameState, key| {
+            captures_ctrl_combo(
+                &state.kb_manager.borrow(),
+                &state.ctrl_passthrough.borrow(),
+                key,
+            )
+        };
+
+        // Copy is left to the browser even though it's bound, unbound combos always are
+        assert!(!captures(&state, Key::C));
+        assert!(captures(&state, Key::Z));
+        assert!(!captures(&state, Key::Y));
+        // Function keys go through the same path
+        assert!(captures(&state, Key::F5));
+        assert!(!captures(&state, Key::F6));
+
+        state.set_ctrl_passthrough("c".into(), false).unwrap();
+        assert!(captures(&state, Key::C));
+        state.set_ctrl_passthrough("z".into(), true).unwrap();
+        assert!(!captures(&state, Key::Z));
+        assert!(state.set_ctrl_passthrough("Ctrl".into(), true).is_err());
+    }
+
+    #[test]
+    fn test_aria_grid_state() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.focused_col = 2;
+        state.apply_action(&Action::WriteCell(1));
+        state.focused_col = 3;
+        state.apply_action(&Action::WriteCell(8));
+
+        // Both sides of a conflict are marked
+        let labels = state.aria_grid_state();
+        assert_eq!(labels.len(), 81);
+        assert_eq!(
+            labels[..5],
+            [
+                "empty",
+                "1, given, conflict",
+                "1, entered, conflict",
+                "8, entered",
+                "2, given"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_focus_anchors() {
+        let mut state = new_state();
+        let parse = |s: &str| Action::try_from(s.to_owned()).unwrap();
+
+        state.apply_action(&parse("focusCenter()"));
+        assert_eq!(state.focused_cell_coord(), (4, 4));
+        state.apply_action(&parse("focusCorner(bottomLeft)"));
+        assert_eq!(state.focused_cell_coord(), (8, 0));
+        state.apply_action(&parse("focuscorner(topright)"));
+        assert_eq!(state.focused_cell_coord(), (0, 8));
+        assert!(Action::try_from("focuscorner(middle)".to_owned()).is_err());
+
+        // Anchors follow the size of the grid
+        state
+            .generate_board("EXAMPLE_SEED".into(), 20, GridVariant::Six)
+            .unwrap();
+        state.apply_action(&parse("focusCorner(bottomRight)"));
+        assert_eq!(state.focused_cell_coord(), (5, 5));
+    }
+
+    #[test]
+    fn test_hud_stats() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        state.solution = solution;
+
+        state.apply_action(&Action::WriteCell(solution[0][0]));
+        state.apply_action(&Action::MoveCol(2, true));
+        state.apply_action(&Action::WriteCell(solution[0][2] % 9 + 1));
+        // Writing over a given changes nothing
+        state.apply_action(&Action::MoveCol(-1, true));
+        state.apply_action(&Action::WriteCell(5));
+        assert_eq!(state.moves(), 2);
+        assert_eq!(state.mismatch_count(), 1);
+
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.moves(), 2);
+        assert_eq!(state.mismatch_count(), 0);
+    }
+
+    #[test]
+    fn test_fill_box_singles() {
+        let mut state = new_state();
+        // Two singles and a cell with two candidates in the top-left box, a single outside
+        for (col, digits) in [(0, vec![4]), (1, vec![5, 6]), (2, vec![7]), (3, vec![8])] {
+            state.grid[0][col].candidates = candidates(&digits);
+        }
+        state.focused_col = 1;
+        state.apply_action(&Action::FillBoxSingles);
+
+        let digits: Vec<Option<u8>> = state.grid[0][..4].iter().map(|c| c.digit).collect();
+        assert_eq!(digits, [Some(4), None, Some(7), None]);
+        assert_eq!(state.grid[0][0].candidates, [0; 9]);
+
+        // Undone in a single step
+        state.apply_action(&Action::Undo);
+        assert!(state.grid[0][..3].iter().all(|c| c.digit.is_none()));
+        state.apply_action(&Action::Redo);
+
+        // Nothing left to fill
+        state.apply_action(&Action::FillBoxSingles);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_fill_box_singles_writes_like_player() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        state.set_auto_prune_candidates(true);
+        let solution = state.solution[0][0];
+        let wrong = solution % 9 + 1;
+        state.grid[0][0].candidates = candidates(&[solution]);
+        state.grid[0][2].candidates = candidates(&[wrong]);
+        // Peer of (0, 0) outside of its box
+        state.grid[0][5].candidates = candidates(&[solution, wrong]);
+
+        state.apply_action(&Action::FillBoxSingles);
+        assert_eq!(state.grid[0][0].digit, Some(solution));
+        assert_eq!(state.grid[0][5].candidates, [0; 9]);
+        // Written singles count as mistakes when wrong
+        assert_eq!(state.mistakes(), 1);
+    }
+
+    #[test]
+    fn test_mark_forced_candidates() {
+        let mut state = new_state();
+        // (0, 8) sees every digit but 9, (8, 8) sees every digit but 1
+        for col in 0..8 {
+            state.grid[0][col].digit = Some(col as u8 + 1);
+        }
+        for row in 1..8 {
+            state.grid[row][8].digit = Some(row as u8 + 1);
+        }
+        state.grid[8][0].digit = Some(9);
+        state.grid[8][8].candidates = candidates(&[1, 4]);
+
+        state.apply_action(&Action::MarkForcedCandidates);
+        assert!(!state.last_action_rejected());
+        assert_eq!(state.grid[0][8].candidates, candidates(&[9]));
+        assert_eq!(state.grid[8][8].candidates, candidates(&[1]));
+        // Nothing is committed, and other cells are untouched
+        assert_eq!(state.grid[0][8].digit, None);
+        assert_eq!(state.grid[4][4].candidates, [0; 9]);
+
+        // Undone in a single step
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][8].candidates, [0; 9]);
+        assert_eq!(state.grid[8][8].candidates, candidates(&[1, 4]));
+
+        assert!(Action::try_from("markForced()".to_owned()).is_ok());
+        let mut state = new_state();
+        state.apply_action(&Action::MarkForcedCandidates);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_editor_confirm_overwrite() {
+        let mut state = new_state();
+        state.set_editor_confirm_overwrite(true);
+
+        // Empty cells and identical digits don't need confirmation
+        state.set_given(0, 0, 5);
+        state.set_given(0, 0, 5);
+        assert_eq!(state.pending_overwrite(), None);
+
+        state.set_given(0, 0, 7);
+        assert_eq!(state.grid[0][0].digit, Some(5));
+        assert_eq!(state.pending_overwrite(), Some(vec![0, 0, 7]));
+        assert!(state.confirm_pending());
+        assert_eq!(state.grid[0][0].digit, Some(7));
+        assert!(!state.confirm_pending());
+
+        // Erasing also needs confirmation
+        state.set_given(0, 0, 0);
+        state.cancel_pending();
+        assert!(!state.confirm_pending());
+        assert_eq!(state.grid[0][0].digit, Some(7));
+    }
+
+    #[test]
+    fn test_auto_complete() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        state.solution = solution;
+
+        // Fill all but the first columns, only singles are left
+        for (row, digits) in solution.iter().enumerate() {
+            for (cell, &digit) in state.grid[row].iter_mut().zip(digits).skip(3) {
+                cell.digit = Some(digit);
+            }
+        }
+        state.grid[0][0].candidates = candidates(&[6, 8]);
+        assert!(state.auto_complete());
+        assert_eq!(state.grid[0][0].digit, Some(solution[0][0]));
+        assert_eq!(state.grid[0][0].candidates, [0; 9]);
+        assert!(state.digits_flat().iter().all(|&d| d != 0));
+
+        // Undone in a single step, and nothing left to complete afterwards
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][0].digit, None);
+        state.apply_action(&Action::Redo);
+        assert!(!state.auto_complete());
+
+        // Never completes a board with a wrong digit
+        state.apply_action(&Action::Undo);
+        state.focused_col = 0;
+        state.apply_action(&Action::WriteCell(solution[0][0] % 9 + 1));
+        state.apply_action(&Action::AutoComplete);
+        assert!(state.last_action_rejected());
+    }
+
+    #[test]
+    fn test_solution_for_current() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        assert_eq!(state.solution_for_current(), Some(solution.concat()));
+
+        // A digit conflicting with a given makes the board unsolvable
+        state.apply_action(&Action::WriteCell(1));
+        assert_eq!(state.solution_for_current(), None);
+    }
+
+    #[test]
+    fn test_win_detection() {
+        let mut state =
+            GameState::new(KeybindManager::with_config("Backspace = clearCell()").unwrap());
+        let given_rows: Vec<Vec<u8>> = UNIQUE_GRID.iter().map(|r| r.to_vec()).collect();
+        let solution = generation::unique_solution(&given_rows, GridShape::CLASSIC).unwrap();
+        state.set_classic_board(&given_rows, solution);
+        let press_backspace = |state: &mut GameState| {
+            *state.last_key.borrow_mut() = Some(Keybind {
+                key: Key::Backspace,
+                modifier: None,
+            });
+            state.update();
+        };
+
+        // Fill every cell but the focused one
+        for (row, digits) in solution.iter().enumerate() {
+            for (cell, &digit) in state.grid[row].iter_mut().zip(digits) {
+                cell.digit = Some(digit);
+            }
+        }
+        press_backspace(&mut state);
+        assert!(!state.is_complete());
+        assert!(!state.has_won());
+
+        state.grid[0][0].digit = Some(solution[0][0]);
+        state.update();
+        assert!(state.is_complete());
+        assert!(state.has_won());
+
+        // Editing the board afterwards doesn't take the win back, restarting does
+        press_backspace(&mut state);
+        assert!(!state.is_complete());
+        assert!(state.has_won());
+        state.apply_action(&Action::Restart);
+        assert!(!state.has_won());
+    }
+
+    #[test]
+    fn test_cell_colors() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let colors: Vec<u8> = (0..=CELL_COLOR_COUNT)
+            .map(|_| {
+                state.apply_action(&Action::CycleColor);
+                state.get_focused_cell().color
+            })
+            .collect();
+        let mut expected: Vec<u8> = (1..=CELL_COLOR_COUNT).collect();
+        expected.push(0);
+        assert_eq!(colors, expected);
+
+        // Givens can be colored too
+        state.apply_action(&Action::CycleColor);
+        state.focused_col = 1;
+        state.apply_action(&Action::CycleColor);
+        assert_eq!(state.grid()[0][1].color, 1);
+
+        state.apply_action(&Action::ClearAllColors);
+        assert!(state.grid().iter().flatten().all(|c| c.color == 0));
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid()[0][0].color, 1);
+    }
+
+    #[test]
+    fn test_highlighted_cells() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::SetCandidate(1));
+        state.apply_action(&Action::HighlightDigit(1));
+
+        // Given 1 of (0, 1), candidate 1 of (0, 0)
+        assert!(state.is_digit_highlighted(0, 1));
+        assert!(!state.is_digit_highlighted(0, 0));
+        assert!(state.has_highlighted_candidate(0, 0));
+        assert!(!state.has_highlighted_candidate(0, 1));
+
+        state.apply_action(&Action::ClearHighlight);
+        assert!(!state.is_digit_highlighted(0, 1));
+        assert!(!state.has_highlighted_candidate(0, 0));
+    }
+
+    #[test]
+    fn test_is_cell_wrong() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        let expected = state.expected_value(0, 0);
+
+        assert!(!state.is_cell_wrong(0, 0));
+        state.apply_action(&Action::WriteCell(expected));
+        assert!(!state.is_cell_wrong(0, 0));
+        state.apply_action(&Action::WriteCell(expected % 9 + 1));
+        assert!(state.is_cell_wrong(0, 0));
+        assert_eq!(state.cell_error(0, 0), Some(CellError::Mismatch));
+
+        // Givens are left alone, even if edited after the solution was computed
+        state.set_given(0, 1, 9);
+        assert!(state.has_mismatch(0, 1));
+        assert!(!state.is_cell_wrong(0, 1));
+    }
+
+    #[test]
+    fn test_toggle_errors() {
+        let mut state = new_state();
+        let toggle = Action::try_from("toggleErrors()".to_owned()).unwrap();
+        assert_eq!(toggle.kind(), ActionKind::ToggleErrors);
+
+        state.apply_action(&Action::SetErrorMode(ErrorMode::RuleConflicts));
+        state.apply_action(&toggle);
+        assert_eq!(state.error_mode(), ErrorMode::Off);
+        state.apply_action(&toggle);
+        assert_eq!(state.error_mode(), ErrorMode::RuleConflicts);
+
+        state.set_show_errors(false);
+        assert_eq!(state.error_mode(), ErrorMode::Off);
+        state.set_show_errors(true);
+        assert_eq!(state.error_mode(), ErrorMode::RuleConflicts);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        assert!(state.conflicts().is_empty());
+
+        // Row conflict with the given 4 at (0, 8)
+        state.apply_action(&Action::WriteCell(4));
+        assert_eq!(state.conflicts(), vec![(0, 0), (0, 8)]);
+        state.apply_action(&Action::ClearCell);
+
+        // Box conflict with the given 7 at (2, 0), not sharing a row or column
+        state.apply_action(&Action::MoveRow(1, true));
+        state.apply_action(&Action::MoveCol(1, true));
+        state.apply_action(&Action::WriteCell(7));
+        assert_eq!(state.conflicts(), vec![(1, 1), (2, 0)]);
+        state.apply_action(&Action::ClearCell);
+        assert!(state.conflicts().is_empty());
+
+        // Givens conflicting with eachother are reported too
+        let mut grid = UNIQUE_GRID;
+        grid[0][0] = 3;
+        let state = state_with_givens(&grid);
+        assert_eq!(state.conflicts(), vec![(0, 0), (0, 6)]);
+    }
+
+    #[test]
+    fn test_load_puzzle() {
+        let puzzle =
+            "..3.2.6..9..3.5..1..18.64....81.29..7.......8..67.82....26.95..8..2.3..9..5.1.3..";
+        let mut state = new_state();
+        state.load_puzzle(puzzle).unwrap();
+        assert_eq!(state.puzzle_string().unwrap(), puzzle);
+        assert!(state.grid[0][2].is_given);
+        assert_eq!(state.grid[0][2].digit, Some(3));
+        assert!(state.solution.iter().flatten().all(|&d| d != 0));
+        assert_eq!(state.solution[0][2], 3);
+
+        // Invalid puzzles leave the board untouched
+        assert!(matches!(
+            state.load_puzzle("123"),
+            Err(LoadPuzzleError::Format(_))
+        ));
+        let contradiction = "11".to_owned() + &".".repeat(79);
+        assert!(matches!(
+            state.load_puzzle(&contradiction),
+            Err(LoadPuzzleError::NoSolution)
+        ));
+        assert!(matches!(
+            state.load_puzzle(&".".repeat(81)),
+            Err(LoadPuzzleError::MultipleSolutions)
+        ));
+        assert_eq!(state.puzzle_string().unwrap(), puzzle);
+
+        state
+            .generate_board("EXAMPLE_SEED".into(), 20, GridVariant::Six)
+            .unwrap();
+        assert_eq!(state.puzzle_string(), Err(PuzzleFormatError::GridSize(6)));
+    }
+
+    #[test]
+    fn test_export_import_state() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+        state.apply_action(&Action::WriteCell(8));
+        state.apply_action(&Action::MoveRow(1, true));
+        state.apply_action(&Action::ToggleCandidate(3));
+        state.apply_action(&Action::CycleColor);
+        state.set_error_mode(ErrorMode::RuleConflicts);
+
+        // Without the solution, it gets solved again from the givens
+        let json = state.export_state(false);
+        assert!(!json.contains("solution"));
+        let mut restored = new_state();
+        restored.import_state(&json).unwrap();
+        assert_eq!(restored.grid, state.grid);
+        assert_eq!(restored.solution, state.solution);
+        assert_eq!(restored.focused_cell_coord(), (1, 0));
+        assert_eq!(restored.error_mode(), ErrorMode::RuleConflicts);
+        assert!(!restored.is_dirty());
+
+        let json = state.export_state(true);
+        let mut restored = new_state();
+        restored.apply_action(&Action::SetMark('a'));
+        restored.pending_overwrite = Some((0, 0, 1));
+        restored.import_state(&json).unwrap();
+        assert_eq!(restored.grid, state.grid);
+        assert_eq!(restored.solution, state.solution);
+        assert!(restored.marks.is_empty());
+        assert_eq!(restored.pending_overwrite(), None);
+
+        // Invalid saves leave the board untouched
+        assert!(matches!(
+            restored.import_state("{}"),
+            Err(ImportStateError::Json(_))
+        ));
+        let resized = json.replacen("\"size\":9", "\"size\":6", 1);
+        assert!(matches!(
+            restored.import_state(&resized),
+            Err(ImportStateError::Shape)
+        ));
+        assert_eq!(restored.grid, state.grid);
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+        let hint = Action::try_from("hint()".to_owned()).unwrap();
+        assert_eq!(hint.kind(), ActionKind::Hint);
+
+        state.apply_action(&Action::ToggleCandidate(2));
+        state.apply_action(&hint);
+        let cell = state.grid[0][0];
+        assert_eq!(cell.digit, Some(state.expected_value(0, 0)));
+        assert!(cell.is_hint);
+        assert_eq!(cell.candidates, [0; 9]);
+        assert_eq!(state.cell_error(0, 0), None);
+
+        // Filled and given cells are left as is
+        state.apply_action(&hint);
+        assert!(state.last_action_rejected);
+        state.apply_action(&Action::MoveCol(1, true));
+        state.apply_action(&hint);
+        assert!(state.last_action_rejected);
+        assert!(!state.grid[0][1].is_hint);
+
+        // Overwriting a hint makes it a regular entry
+        state.apply_action(&Action::MoveCol(-1, true));
+        state.apply_action(&Action::ClearCell);
+        assert!(!state.grid[0][0].is_hint);
+    }
+
+    #[test]
+    fn test_clear_mistakes() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.recompute_solution().unwrap();
+        let clear = Action::try_from("clearMistakes()".to_owned()).unwrap();
+        assert_eq!(clear.kind(), ActionKind::ClearMistakes);
+        state.apply_action(&clear);
+        assert!(state.last_action_rejected);
+
+        let mut write = |row: usize, col: usize, digit: u8| {
+            (state.focused_row, state.focused_col) = (row as u8, col as u8);
+            state.apply_action(&Action::WriteCell(digit));
+        };
+        // Solution of the first row is 6 1 9 7 2 8 3 5 4
+        write(0, 0, 6);
+        write(0, 2, 5);
+        write(0, 3, 7);
+        write(0, 5, 1);
+        state.focused_col = 7;
+        state.apply_action(&Action::ToggleCandidate(5));
+
+        state.apply_action(&clear);
+        let row: Vec<Option<u8>> = state.grid[0].iter().map(|cell| cell.digit).collect();
+        assert_eq!(
+            row,
+            [
+                Some(6),
+                Some(1),
+                None,
+                Some(7),
+                Some(2),
+                None,
+                Some(3),
+                None,
+                Some(4)
+            ]
+        );
+        assert!(state.grid[0][7].has_candidate(5));
+
+        // Cleared in a single undo step
+        state.apply_action(&Action::Undo);
+        assert_eq!(state.grid[0][2].digit, Some(5));
+        assert_eq!(state.grid[0][5].digit, Some(1));
+    }
+
+    #[test]
+    fn test_fill_candidates() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let fill = Action::try_from("fillCandidates()".to_owned()).unwrap();
+        assert_eq!(fill.kind(), ActionKind::FillCandidates);
+
+        // Outdated candidate, 1 is given in the same row
+        state.apply_action(&Action::ToggleCandidate(1));
+        state.apply_action(&Action::MoveCol(2, true));
+        state.apply_action(&Action::WriteCell(9));
+        state.apply_action(&fill);
+
+        // (0, 0) sees 1 2 3 4 9 in its row, 7 5 9 2 in its column and 1 2 7 in its box
+        assert_eq!(state.grid[0][0].candidates, candidates(&[6, 8]));
+        // Filled cells are skipped
+        assert_eq!(state.grid[0][2].candidates, [0; 9]);
+        assert_eq!(state.grid[0][1].candidates, [0; 9]);
+    }
+
+    #[test]
+    fn test_clear_all_candidates() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.apply_action(&Action::FillCandidates);
+        state.grid[8][8].candidates[2] = 1;
+        assert!(state.grid.iter().flatten().any(|c| c.candidates != [0; 9]));
+
+        let clear = Action::try_from("clearAllCandidates()".to_owned()).unwrap();
+        assert_eq!(clear.kind(), ActionKind::ClearAllCandidates);
+        state.apply_action(&clear);
+        assert!(state.grid.iter().flatten().all(|c| c.candidates == [0; 9]));
+        // Digits are left alone
+        assert_eq!(state.givens(), UNIQUE_GRID.map(|r| r.to_vec()).to_vec());
+    }
+
+    #[test]
+    fn test_next_prev_empty() {
+        let mut grid = [[1; 9]; 9];
+        grid[0][4] = 0;
+        grid[3][0] = 0;
+        grid[8][8] = 0;
+        let mut state = state_with_givens(&grid);
+        let next = Action::try_from("nextEmpty()".to_owned()).unwrap();
+        let prev = Action::try_from("prevEmpty()".to_owned()).unwrap();
+
+        let mut visited = Vec::new();
+        for _ in 0..4 {
+            state.apply_action(&next);
+            visited.push(state.focused_cell_coord());
+        }
+        assert_eq!(visited, [(0, 4), (3, 0), (8, 8), (0, 4)]);
+
+        visited.clear();
+        for _ in 0..4 {
+            state.apply_action(&prev);
+            visited.push(state.focused_cell_coord());
+        }
+        assert_eq!(visited, [(8, 8), (3, 0), (0, 4), (8, 8)]);
+
+        // Nowhere to go once the board is full
+        state.set_given(0, 4, 1);
+        state.set_given(3, 0, 1);
+        state.set_given(8, 8, 1);
+        state.apply_action(&next);
+        assert_eq!(state.focused_cell_coord(), (8, 8));
+    }
+
+    #[test]
+    fn test_set_color() {
+        let parse = |s: &str| Action::try_from(s.to_owned());
+        assert!(matches!(parse("setColor(0)"), Ok(Action::SetColor(0))));
+        assert!(matches!(parse("setColor(2)"), Ok(Action::SetColor(2))));
+        assert!(parse(&format!("setColor({})", CELL_COLOR_COUNT + 1)).is_err());
+        assert!(parse("setColor(-1)").is_err());
+
+        let mut state = new_state();
+        for color in (1..=CELL_COLOR_COUNT).chain([0]) {
+            state.apply_action(&Action::SetColor(color));
+            assert_eq!(state.grid[0][0].color, color);
+        }
+    }
+
+    #[test]
+    fn test_multiple_actions_order() {
+        let config = "x = toggleCandidate(3); moveRow(1, true); writeCell(5)";
+        let mut state = GameState::new(KeybindManager::with_config(config).unwrap());
+        *state.last_key.borrow_mut() = Some(Keybind {
+            key: Key::X,
+            modifier: None,
+        });
+        state.update();
+
+        assert_eq!(state.grid[0][0].candidates, candidates(&[3]));
+        assert_eq!(state.focused_cell_coord(), (1, 0));
+        assert_eq!(state.grid[1][0].digit, Some(5));
+        assert_eq!(state.history().len(), 3);
+    }
+
+    #[test]
+    fn test_focus_cell() {
+        let parse = |s: &str| Action::try_from(s.to_owned());
+        assert!(matches!(
+            parse("focusCell(3, 8)"),
+            Ok(Action::FocusCell(3, 8))
+        ));
+        assert!(parse("focusCell(9, 0)").is_err());
+        assert!(parse("focusCell(0, -1)").is_err());
+        assert!(parse("focusCell(4)").is_err());
+
+        let mut state = new_state();
+        state.apply_action(&Action::FocusCell(3, 8));
+        assert_eq!(state.focused_cell_coord(), (3, 8));
+
+        state.set_focus(5, 1).unwrap();
+        assert_eq!(state.focused_cell_coord(), (5, 1));
+        assert_eq!(state.set_focus(9, 1), Err(CellOutOfRange(9, 1)));
+        assert_eq!(state.focused_cell_coord(), (5, 1));
+    }
+
+    #[test]
+    fn test_next_prev_box() {
+        let mut state = new_state();
+        let next = Action::try_from("nextBox()".to_owned()).unwrap();
+        let prev = Action::try_from("prevBox()".to_owned()).unwrap();
+
+        // Starts from the middle of the first box
+        state.set_focus(1, 2).unwrap();
+        let mut visited = Vec::new();
+        for _ in 0..9 {
+            state.apply_action(&next);
+            visited.push(state.focused_cell_coord());
+        }
+        assert_eq!(
+            visited,
+            [
+                (0, 3),
+                (0, 6),
+                (3, 0),
+                (3, 3),
+                (3, 6),
+                (6, 0),
+                (6, 3),
+                (6, 6),
+                (0, 0)
+            ]
+        );
+
+        state.apply_action(&prev);
+        assert_eq!(state.focused_cell_coord(), (6, 6));
+        state.set_focus(4, 5).unwrap();
+        state.apply_action(&prev);
+        assert_eq!(state.focused_cell_coord(), (3, 0));
+    }
+
+    #[test]
+    fn test_digit_counts() {
+        let mut grid = UNIQUE_GRID;
+        // Add the missing 2s, one per row
+        for (row, col) in [(2, 1), (3, 7), (5, 1), (6, 0)] {
+            grid[row][col] = 2;
+        }
+        let state = state_with_givens(&grid);
+
+        assert_eq!(state.digit_counts(), [5, 9, 3, 4, 4, 4, 3, 2, 2]);
+        assert_eq!(state.digit_counts_flat(), state.digit_counts().to_vec());
+        let mut completed = [false; 9];
+        completed[1] = true;
+        assert_eq!(state.completed_digits(), completed);
+        assert_eq!(state.completed_digits_flat(), [0, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_timer() {
+        let mut state = new_state();
+        state.tick_timer_at(500.);
+        assert_eq!(state.elapsed_ms(), 0.);
+
+        state.start_timer_at(1000.);
+        state.tick_timer_at(1250.);
+        state.tick_timer_at(2000.);
+        assert_eq!(state.elapsed_ms(), 1000.);
+        // Starting again doesn't reset the start time
+        state.start_timer_at(3000.);
+        state.tick_timer_at(3500.);
+        assert_eq!(state.elapsed_ms(), 2500.);
+
+        // Stops accumulating once won
+        state.won = true;
+        state.tick_timer_at(4000.);
+        assert_eq!(state.elapsed_ms(), 3000.);
+        state.tick_timer_at(9000.);
+        state.start_timer_at(9000.);
+        state.tick_timer_at(9500.);
+        assert_eq!(state.elapsed_ms(), 3000.);
+
+        // Reset along with the other stats of the game
+        state.reset_progress();
+        assert_eq!(state.elapsed_ms(), 0.);
+        assert!(!state.has_won());
+    }
+
+    #[test]
+    fn test_mistakes() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        // No solution known yet
+        state.apply_action(&Action::WriteCell(5));
+        assert_eq!(state.mistakes(), 0);
+
+        state.recompute_solution().unwrap();
+        // Solution of (0, 0) is 6
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.mistakes(), 0);
+        state.apply_action(&Action::WriteCell(5));
+        assert_eq!(state.mistakes(), 1);
+        state.apply_action(&Action::WriteCell(5));
+        assert_eq!(state.mistakes(), 1);
+        state.apply_action(&Action::WriteCell(8));
+        assert_eq!(state.mistakes(), 2);
+
+        // Fixing the digit keeps the count
+        state.apply_action(&Action::Undo);
+        state.apply_action(&Action::WriteCell(6));
+        assert_eq!(state.mistakes(), 2);
+
+        state.reset_mistakes();
+        assert_eq!(state.mistakes(), 0);
+    }
+
+    #[test]
+    fn test_new_game() {
+        let mut state = new_state();
+        state.new_game("EXAMPLE_SEED".into(), 30).unwrap();
+        let first = state.grid.clone();
+
+        // Play a bit before starting over
+        state.set_focus(4, 4).unwrap();
+        state.apply_action(&Action::HighlightDigit(3));
+        let (row, col) = state.empty_cells()[0];
+        state.set_focus(row, col).unwrap();
+        state.apply_action(&Action::WriteCell(
+            state.expected_value(row.into(), col.into()) % 9 + 1,
+        ));
+        assert_eq!(state.mistakes(), 1);
+
+        state.new_game("EXAMPLE_SEED".into(), 30).unwrap();
+        assert_eq!(state.grid, first);
+        assert_eq!(state.focused_cell_coord(), (0, 0));
+        assert_eq!(state.highlighted_digit(), None);
+        assert_eq!(state.mistakes(), 0);
+        assert_eq!(state.moves(), 0);
+        assert!(state.undo_stack.undo.is_empty());
+
+        // A count too low to keep the solution unique still loads the closest puzzle
+        let actual_count = state.new_game("EXAMPLE_SEED".into(), 17).unwrap();
+        assert!(actual_count > 17);
+        assert_eq!(
+            state.givens().iter().flatten().filter(|&&d| d != 0).count(),
+            actual_count
+        );
+        assert!(generation::unique_solution(&state.givens(), state.shape).is_ok());
+        let givens = state.givens();
+        state.apply_action(&Action::NewGame);
+        assert!(!state.last_action_rejected);
+        assert_ne!(state.givens(), givens);
+    }
+
+    #[test]
+    fn test_next_logical_step() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        let step = state.next_logical_step().unwrap();
+        assert!(step.contains("single"));
+
+        // A filled board has nothing left to deduce
+        let solution = generation::unique_solution(&state.givens(), state.shape).unwrap();
+        for (row, digits) in solution.iter().enumerate() {
+            for (col, &digit) in digits.iter().enumerate() {
+                state.grid[row][col].digit = Some(digit);
+            }
+        }
+        assert_eq!(state.next_logical_step(), None);
+
+        // Pencil marks narrow the candidates down: with only 9 marked in the first cell of an
+        // otherwise empty board, it's a naked single
+        let mut state = new_state();
+        state.grid[0][0].candidates[8] = 1;
+        assert_eq!(
+            state.next_logical_step().as_deref(),
+            Some("Naked single: 9 is the only candidate left at row 1, column 1")
+        );
+    }
+
+    #[test]
+    fn test_flat_layout() {
+        let mut state = state_with_givens(&UNIQUE_GRID);
+        state.grid[2][5].candidates = candidates(&[1, 9]);
+        state.grid[8][7].candidates = [0, 2, 0, 0, 3, 0, 0, 0, 0];
+
+        // Row-major, cell (row, col) at index `row * size + col`
+        let digits = state.digits_flat();
+        let masks = state.candidate_masks_flat();
+        assert_eq!(digits.len(), 81);
+        assert_eq!(masks.len(), 81);
+        for (row, cells) in state.grid.iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                assert_eq!(digits[row * 9 + col], cell.digit.unwrap_or(0));
+                for n in 0..9 {
+                    let in_mask = masks[row * 9 + col] & (1 << n) != 0;
+                    assert_eq!(in_mask, cell.has_candidate(n + 1), "({row}, {col}) {n}");
+                }
+            }
+        }
+        // Bit `n` is candidate `n + 1`, whatever its pencil color
+        assert_eq!(masks[2 * 9 + 5], 0b1_0000_0001);
+        assert_eq!(masks[8 * 9 + 7], 0b1_0010);
+
+        // Smaller grids only have their own cells
+        state
+            .generate_board("EXAMPLE_SEED".into(), 20, GridVariant::Six)
+            .unwrap();
+        assert_eq!(state.digits_flat().len(), 36);
+        assert_eq!(
+            state.digits_flat()[6 + 2],
+            state.grid[1][2].digit.unwrap_or(0)
+        );
     }
 }
@@ -1,109 +1,845 @@
 use rand::{seq::SliceRandom, Rng};
 use rand_pcg::Pcg64;
 use rand_seeder::Seeder;
+use serde::Deserialize;
+use std::{collections::HashSet, ops::RangeInclusive};
+use thiserror::Error;
 
-/// Returns a tuple containing the fully solved grid, as well as a masked version of the grid
+use wasm_bindgen::prelude::*;
+
+use crate::solver;
+
+/// Digits of a grid by row, 0 meaning an empty cell
+pub type Grid = Vec<Vec<u8>>;
+
+/// Digits of a grid of up to 9x9 cells by row, 0 meaning an empty cell. Unlike a `Grid`, it
+/// lives on the stack, which makes it cheap to copy and index while generating and solving.
+/// Smaller grids use its top-left corner, the other cells staying empty.
+pub type Board = [[u8; 9]; 9];
+
+/// Copies a grid of up to 9x9 cells into the top-left corner of a `Board`
+pub fn board_from_grid<R: AsRef<[u8]>>(grid: &[R]) -> Board {
+    let mut board = [[0; 9]; 9];
+    for (board_row, row) in board.iter_mut().zip(grid) {
+        let row = row.as_ref();
+        board_row[..row.len()].copy_from_slice(row);
+    }
+    board
+}
+
+/// Returns the top-left `size` by `size` cells of a board as a `Grid`
+pub fn board_to_grid(board: &Board, size: usize) -> Grid {
+    board[..size]
+        .iter()
+        .map(|row| row[..size].to_vec())
+        .collect()
+}
+
+/// Returns the top-left `size` by `size` cells of a board in row-major order
+pub fn board_to_flat(board: &Board, size: usize) -> Vec<u8> {
+    board[..size]
+        .iter()
+        .flat_map(|row| &row[..size])
+        .copied()
+        .collect()
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GenerationError {
+    #[error("The puzzle has no solution")]
+    NoSolution,
+    #[error("The puzzle has more than one solution")]
+    MultipleSolutions,
+    /// Filling an empty grid should always succeed, this means the generator is broken
+    #[error("Unable to fill grid")]
+    FillFailed,
+    #[error("The grid isn't a complete and valid 9x9 or 6x6 grid")]
+    InvalidGrid,
+    #[error("Unable to generate a puzzle of the requested difficulty")]
+    DifficultyNotReached,
+    /// Masking got stuck before removing enough cells, no single removal keeping the solution
+    /// unique (or the minimum givens per box). Holds the puzzle masked as far as possible, which
+    /// is still valid, only with more givens than requested.
+    #[error("Couldn't mask the puzzle down to {requested} givens, stopped at {reached}")]
+    CouldNotReachGivenCount {
+        requested: usize,
+        reached: usize,
+        givens: Grid,
+        solution: Grid,
+    },
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for GenerationError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PuzzleDecodeError {
+    #[error("Expected {expected} bytes, got {0}", expected = Puzzle::BYTE_LEN)]
+    Length(usize),
+    #[error("Invalid digit {1} at cell {0}")]
+    Digit(usize, u8),
+    #[error("The solution isn't a valid filled grid")]
+    InvalidSolution,
+    #[error("Given at cell {0} doesn't match the solution")]
+    GivenMismatch(usize),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for PuzzleDecodeError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// Reasons a puzzle fails `verify_puzzle`
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    #[error("Unsupported grid dimensions")]
+    Shape,
+    #[error("The solution isn't a valid filled grid")]
+    InvalidSolution,
+    #[error("Given at row {0}, column {1} doesn't match the solution")]
+    GivenMismatch(usize, usize),
+    #[error("The givens don't have a unique solution: {0}")]
+    NotUnique(#[from] GenerationError),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for VerifyError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// A 9x9 puzzle along with its solution, as stored in puzzle banks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    /// Given digits, 0 meaning an empty cell
+    pub givens: Vec<Vec<u8>>,
+    pub solution: Vec<Vec<u8>>,
+    /// Difficulty rating of the puzzle, its scale is left to the app providing the puzzles
+    pub difficulty: u8,
+}
+
+impl Puzzle {
+    /// Number of bytes needed to store 81 digits at 4 bits per digit
+    const GRID_BYTE_LEN: usize = 41;
+    /// Size of an encoded puzzle: difficulty, givens, then solution
+    pub const BYTE_LEN: usize = 1 + 2 * Self::GRID_BYTE_LEN;
+
+    /// Encodes the puzzle as its difficulty byte followed by the givens and the solution, each
+    /// digit taking 4 bits (high bits first) in reading order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::BYTE_LEN);
+        bytes.push(self.difficulty);
+        bytes.extend(pack_digits(&self.givens));
+        bytes.extend(pack_digits(&self.solution));
+        bytes
+    }
+
+    /// Decodes a puzzle encoded by `to_bytes`, making sure the solution is a valid filled grid
+    /// and that the givens match it
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PuzzleDecodeError> {
+        if bytes.len() != Self::BYTE_LEN {
+            return Err(PuzzleDecodeError::Length(bytes.len()));
+        }
+        let (givens_bytes, solution_bytes) = bytes[1..].split_at(Self::GRID_BYTE_LEN);
+        let givens = unpack_digits(givens_bytes)?;
+        let solution = unpack_digits(solution_bytes)?;
+
+        let solution_flat = solution.iter().flatten();
+        if solution_flat.clone().any(|&d| d == 0) || has_conflicts(&solution, GridShape::CLASSIC) {
+            return Err(PuzzleDecodeError::InvalidSolution);
+        }
+        if let Some(i) = givens
+            .iter()
+            .flatten()
+            .zip(solution_flat)
+            .position(|(&given, &digit)| given != 0 && given != digit)
+        {
+            return Err(PuzzleDecodeError::GivenMismatch(i));
+        }
+
+        Ok(Self {
+            givens,
+            solution,
+            difficulty: bytes[0],
+        })
+    }
+}
+
+/// Packs the 81 digits of a 9x9 grid two per byte
+fn pack_digits(grid: &[Vec<u8>]) -> Vec<u8> {
+    let digits: Vec<u8> = grid.iter().flatten().copied().collect();
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Unpacks the 81 digits of a 9x9 grid packed by `pack_digits`
+fn unpack_digits(bytes: &[u8]) -> Result<Vec<Vec<u8>>, PuzzleDecodeError> {
+    let digits: Vec<u8> = bytes
+        .iter()
+        .flat_map(|b| [b >> 4, b & 0xF])
+        .take(81)
+        .collect();
+    if let Some(i) = digits.iter().position(|&d| d > 9) {
+        return Err(PuzzleDecodeError::Digit(i, digits[i]));
+    }
+    Ok(digits.chunks(9).map(|row| row.to_vec()).collect())
+}
+
+/// Dimensions of the boxes of a grid. A grid is `size()` cells wide and tall, and uses the
+/// digits `1..=size()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridShape {
+    pub box_rows: usize,
+    pub box_cols: usize,
+}
+
+impl GridShape {
+    /// Standard 9x9 grid with 3x3 boxes
+    pub const CLASSIC: Self = Self {
+        box_rows: 3,
+        box_cols: 3,
+    };
+    /// 6x6 grid with boxes of 2 rows by 3 columns
+    pub const SIX: Self = Self {
+        box_rows: 2,
+        box_cols: 3,
+    };
+
+    /// Returns the shape of the supported grids with `size` rows, i.e. 9x9 and 6x6
+    pub fn from_size(size: usize) -> Option<Self> {
+        match size {
+            9 => Some(Self::CLASSIC),
+            6 => Some(Self::SIX),
+            _ => None,
+        }
+    }
+
+    /// Length of a side of the grid, which is also the number of cells in a box
+    pub fn size(&self) -> usize {
+        self.box_rows * self.box_cols
+    }
+
+    /// Smallest number of givens a puzzle with this shape needs to have a unique solution
+    pub fn min_givens(&self) -> usize {
+        match self.size() {
+            9 => 17,
+            6 => 8,
+            _ => 0,
+        }
+    }
+
+    /// Returns the coordinates of the top-left cell of the box containing the specified cell
+    pub fn box_origin(&self, row: usize, col: usize) -> (usize, usize) {
+        (
+            row / self.box_rows * self.box_rows,
+            col / self.box_cols * self.box_cols,
+        )
+    }
+
+    /// Returns the cells sharing a row, column or box with the specified cell, excluding the
+    /// cell itself
+    pub fn peers(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let size = self.size();
+        let (box_row, box_col) = self.box_origin(row, col);
+
+        let mut peers: Vec<(usize, usize)> = (0..size)
+            .map(|c| (row, c))
+            .chain((0..size).map(|r| (r, col)))
+            .collect();
+        for r in box_row..box_row + self.box_rows {
+            for c in box_col..box_col + self.box_cols {
+                // Box cells on the same row or col are already included
+                if r != row && c != col {
+                    peers.push((r, c));
+                }
+            }
+        }
+        peers.retain(|&cell| cell != (row, col));
+        peers
+    }
+
+    /// Returns the cells of every row, column and box of the grid
+    pub fn units(&self) -> Vec<Vec<(usize, usize)>> {
+        let size = self.size();
+        let rows = (0..size).map(|r| (0..size).map(|c| (r, c)).collect());
+        let cols = (0..size).map(|c| (0..size).map(|r| (r, c)).collect());
+        let boxes = (0..size).map(|b| self.box_cells(b));
+        rows.chain(cols).chain(boxes).collect()
+    }
+
+    /// Returns the index of the box containing a cell, boxes being numbered in reading order
+    /// like in `box_cells`
+    pub fn box_index(&self, row: usize, col: usize) -> usize {
+        let boxes_per_row = self.size() / self.box_cols;
+        row / self.box_rows * boxes_per_row + col / self.box_cols
+    }
+
+    /// Returns the cells of a box, boxes being numbered in reading order starting at 0
+    pub fn box_cells(&self, box_idx: usize) -> Vec<(usize, usize)> {
+        let boxes_per_row = self.size() / self.box_cols;
+        let box_row = box_idx / boxes_per_row * self.box_rows;
+        let box_col = box_idx % boxes_per_row * self.box_cols;
+        (box_row..box_row + self.box_rows)
+            .flat_map(|r| (box_col..box_col + self.box_cols).map(move |c| (r, c)))
+            .collect()
+    }
+}
+
+/// Pattern formed by the givens left by masking
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Symmetry {
+    /// Cells are removed around their jittered mirror position, giving a loosely balanced
+    /// pattern
+    None,
+    /// Unchanged by a half turn around the center of the grid
+    Central,
+    /// Unchanged by flipping the grid upside down
+    Horizontal,
+    /// Unchanged by flipping the grid left to right
+    Vertical,
+}
+
+impl Symmetry {
+    /// Returns the cell paired with `(row, col)` in a grid of side `size`, which is the cell
+    /// itself when it lies on the axis or center of symmetry
+    pub fn mirror(&self, row: usize, col: usize, size: usize) -> (usize, usize) {
+        let last = size - 1;
+        match self {
+            Symmetry::None => (row, col),
+            Symmetry::Central => (last - row, last - col),
+            Symmetry::Horizontal => (last - row, col),
+            Symmetry::Vertical => (row, last - col),
+        }
+    }
+}
+
+/// Difficulty levels of the generated 9x9 puzzles, see `generate_grid_with_difficulty`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    /// Number of givens a puzzle of this difficulty is masked to
+    fn given_range(&self) -> RangeInclusive<usize> {
+        match self {
+            Difficulty::Easy => 36..=40,
+            Difficulty::Medium => 30..=35,
+            Difficulty::Hard => 26..=29,
+            Difficulty::Expert => 22..=25,
+        }
+    }
+
+    /// True if puzzles of this difficulty must need more than naked singles to be solved
+    fn rejects_naked_singles(&self) -> bool {
+        matches!(self, Difficulty::Hard | Difficulty::Expert)
+    }
+}
+
+/// Maximum number of puzzles generated by `generate_grid_with_difficulty` before giving up
+const DIFFICULTY_ATTEMPTS: usize = 20;
+
+/// Settings changing the kind of puzzles generated
+#[derive(Clone, Copy, Debug)]
+pub struct GenerationOptions {
+    pub shape: GridShape,
+    /// Allows masking more cells than possible while keeping a unique solution, in order to
+    /// reach the requested given count. The returned solution is then only one of the puzzle's
+    /// solutions.
+    pub allow_non_unique: bool,
+    /// Minimum number of givens kept in every box
+    pub min_clues_per_box: u8,
+    /// Pattern formed by the givens. Symmetric patterns are masked in mirrored pairs.
+    pub symmetry: Symmetry,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        Self {
+            shape: GridShape::CLASSIC,
+            allow_non_unique: false,
+            min_clues_per_box: 0,
+            symmetry: Symmetry::None,
+        }
+    }
+}
+
+/// Returns a tuple containing the fully solved grid, a masked version of the grid and the
+/// number of givens in the masked grid. Errors with `CouldNotReachGivenCount`, holding the
+/// puzzle with the fewest givens reached, if the puzzle can't have that few givens and still
+/// have a unique solution.
 ///
 /// # Arguments
 /// * `seed_str` - A string used as a seed to generate the grid and its mask
-/// * `given_count` - The number of unmasked cells in the masked grid (>= 17)
-pub fn generate_grid(seed_str: String, given_count: usize) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+/// * `given_count` - The number of unmasked cells in the masked grid (>= 17 for a unique 9x9
+///   grid)
+/// * `options` - The kind of grid to generate
+pub fn generate_grid(
+    seed_str: String,
+    given_count: usize,
+    options: &GenerationOptions,
+) -> Result<(Grid, Grid, usize), GenerationError> {
     let mut rng: Pcg64 = Seeder::from(seed_str).make_rng();
 
-    let filled = generate_random_filled_grid(&mut rng);
-    let masked = mask_grid(filled.clone(), given_count, &mut rng);
-    (filled, masked)
+    let size = options.shape.size();
+    let filled = generate_random_filled_grid(options.shape, &mut rng)?;
+    let masked = mask(filled, given_count, options, &mut rng);
+    let actual_count = check_given_count(&filled, &masked, size, given_count)?;
+    Ok((
+        board_to_grid(&filled, size),
+        board_to_grid(&masked, size),
+        actual_count,
+    ))
+}
+
+/// Same as `generate_grid`, but for a 9x9 puzzle whose given count is picked according to
+/// `difficulty`. Puzzles that couldn't be masked down to the given count range of the
+/// difficulty, and Hard and Expert puzzles that can be solved with naked singles alone, are
+/// rejected and generated again. The puzzle always has a unique solution.
+///
+/// Errors with `CouldNotReachGivenCount` holding the puzzle with the fewest givens if every
+/// attempt had too many givens, or `DifficultyNotReached` if none was hard enough.
+pub fn generate_grid_with_difficulty(
+    seed: String,
+    difficulty: Difficulty,
+) -> Result<(Grid, Grid, usize), GenerationError> {
+    let mut rng: Pcg64 = Seeder::from(seed).make_rng();
+    let options = GenerationOptions::default();
+    let max_givens = *difficulty.given_range().end();
+
+    // Puzzle with the fewest givens among the ones with too many, as (givens, solution, count)
+    let mut closest: Option<(Grid, Grid, usize)> = None;
+    for _ in 0..DIFFICULTY_ATTEMPTS {
+        let given_count = rng.gen_range(difficulty.given_range());
+        let filled = generate_random_filled_grid(options.shape, &mut rng)?;
+        let masked = mask(filled, given_count, &options, &mut rng);
+        let masked = board_to_grid(&masked, options.shape.size());
+        if difficulty.rejects_naked_singles()
+            && solver::solves_with_naked_singles(&masked, options.shape)
+        {
+            continue;
+        }
+        let actual_count = get_unmasked_cells(&masked).len();
+        if actual_count > max_givens {
+            if closest
+                .as_ref()
+                .is_none_or(|(_, _, count)| actual_count < *count)
+            {
+                closest = Some((masked, board_to_grid(&filled, 9), actual_count));
+            }
+            continue;
+        }
+        return Ok((board_to_grid(&filled, 9), masked, actual_count));
+    }
+    match closest {
+        Some((givens, solution, reached)) => Err(GenerationError::CouldNotReachGivenCount {
+            requested: max_givens,
+            reached,
+            givens,
+            solution,
+        }),
+        None => Err(GenerationError::DifficultyNotReached),
+    }
+}
+
+/// Generates a 9x9 puzzle of the specified difficulty, see `generate_grid_with_difficulty`.
+/// Returns it encoded by `Puzzle::to_bytes`, with the difficulty stored as its index in
+/// `Difficulty`, ready for `GameState::load_from_bytes`.
+#[wasm_bindgen]
+pub fn difficulty_puzzle_bytes(
+    seed: String,
+    difficulty: Difficulty,
+) -> Result<Vec<u8>, GenerationError> {
+    let (solution, givens, _) = generate_grid_with_difficulty(seed, difficulty)?;
+    let puzzle = Puzzle {
+        givens,
+        solution,
+        difficulty: difficulty as u8,
+    };
+    Ok(puzzle.to_bytes())
+}
+
+/// Generates `count` distinct 9x9 puzzles from a single seed, the same seed always giving the
+/// same puzzles. Their difficulty is left unrated (0).
+pub fn generate_batch(
+    seed: String,
+    count: usize,
+    given_count: usize,
+) -> Result<Vec<Puzzle>, GenerationError> {
+    let mut rng: Pcg64 = Seeder::from(seed).make_rng();
+    let options = GenerationOptions::default();
+
+    let mut seen = HashSet::new();
+    let mut puzzles = Vec::with_capacity(count);
+    while puzzles.len() < count {
+        // The RNG keeps advancing, so a collision is simply followed by a different puzzle
+        let solution = generate_random_filled_grid(options.shape, &mut rng)?;
+        let givens = mask(solution, given_count, &options, &mut rng);
+        if seen.insert(givens) {
+            puzzles.push(Puzzle {
+                givens: board_to_grid(&givens, 9),
+                solution: board_to_grid(&solution, 9),
+                difficulty: 0,
+            });
+        }
+    }
+    Ok(puzzles)
+}
+
+/// Masks a known solved grid (e.g. a transformed one) until `given_count` givens remain.
+/// Errors if `solved` isn't a complete and valid 9x9 or 6x6 grid, or if no more cells can be
+/// removed while keeping a unique solution before reaching `given_count`.
+pub fn mask_existing(
+    solved: &[Vec<u8>],
+    given_count: usize,
+    seed: u64,
+    symmetry: Symmetry,
+) -> Result<Grid, GenerationError> {
+    let shape = GridShape::from_size(solved.len()).ok_or(GenerationError::InvalidGrid)?;
+    let size = shape.size();
+    let digits = 1..=size as u8;
+    if solved.iter().any(|r| r.len() != size)
+        || !solved.iter().flatten().all(|d| digits.contains(d))
+        || has_conflicts(solved, shape)
+    {
+        return Err(GenerationError::InvalidGrid);
+    }
+
+    let mut rng: Pcg64 = Seeder::from(seed).make_rng();
+    let options = GenerationOptions {
+        shape,
+        symmetry,
+        ..Default::default()
+    };
+    let filled = board_from_grid(solved);
+    let masked = mask(filled, given_count, &options, &mut rng);
+    check_given_count(&filled, &masked, size, given_count)?;
+    Ok(board_to_grid(&masked, size))
+}
+
+/// Masks a solved 9x9 or 6x6 grid given in row-major order, see `mask_existing`. Returns the
+/// givens in row-major order, 0 meaning an empty cell.
+#[wasm_bindgen]
+pub fn mask_existing_flat(
+    solved: &[u8],
+    given_count: usize,
+    seed: u64,
+    symmetry: Symmetry,
+) -> Result<Vec<u8>, GenerationError> {
+    let size = (solved.len() as f64).sqrt() as usize;
+    if size * size != solved.len() {
+        return Err(GenerationError::InvalidGrid);
+    }
+    let solved: Grid = solved.chunks(size).map(|r| r.to_vec()).collect();
+    Ok(mask_existing(&solved, given_count, seed, symmetry)?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Generates the puzzle of the day from a date string such as "2024-06-01". Every client
+/// generating the puzzle for the same date gets the same puzzle.
+pub fn daily_puzzle(date_seed: &str, given_count: usize) -> Result<Puzzle, GenerationError> {
+    // Prefixed so the daily puzzle differs from a game started with the date as its seed
+    let seed = format!("daily-{date_seed}");
+    let (solution, givens, _) = generate_grid(seed, given_count, &GenerationOptions::default())?;
+    Ok(Puzzle {
+        givens,
+        solution,
+        difficulty: 0,
+    })
+}
+
+/// Generates the puzzle of the day, see `daily_puzzle`. Returns it encoded by
+/// `Puzzle::to_bytes`, ready for `GameState::load_from_bytes`.
+#[wasm_bindgen]
+pub fn daily_puzzle_bytes(date_seed: &str, given_count: usize) -> Result<Vec<u8>, GenerationError> {
+    Ok(daily_puzzle(date_seed, given_count)?.to_bytes())
+}
+
+/// Generates a bank of `count` distinct puzzles from a seed, see `generate_batch`. Returns the
+/// puzzles encoded by `Puzzle::to_bytes` one after the other, each `Puzzle::BYTE_LEN` long.
+#[wasm_bindgen]
+pub fn generate_puzzle_bank(
+    seed: String,
+    count: usize,
+    given_count: usize,
+) -> Result<Vec<u8>, GenerationError> {
+    let puzzles = generate_batch(seed, count, given_count)?;
+    Ok(puzzles.iter().flat_map(Puzzle::to_bytes).collect())
 }
 
 /// Creates a fully completed Sudoku grid
-fn generate_random_filled_grid(rng: &mut Pcg64) -> Vec<Vec<u8>> {
-    let mut grid = vec![vec![0; 9]; 9];
-    // Fill boxes 1, 5 and 9 randomly since they never interact with eachother
-    let offsets = [0, 3, 6];
+fn generate_random_filled_grid(
+    shape: GridShape,
+    rng: &mut Pcg64,
+) -> Result<Board, GenerationError> {
+    let size = shape.size();
+    let mut grid = [[0; 9]; 9];
+    // Fill the boxes along the diagonal (1, 5 and 9 for a 9x9 grid) randomly since they never
+    // interact with eachother
+    let diagonal_len = (size / shape.box_rows).min(size / shape.box_cols);
 
-    for offset in offsets {
-        let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    for i in 0..diagonal_len {
+        let (row_offset, col_offset) = (i * shape.box_rows, i * shape.box_cols);
+        let mut digits: Vec<u8> = (1..=size as u8).collect();
         digits.shuffle(rng);
-        for (i, digit) in digits.iter().enumerate() {
-            let row = (i / 3) + offset;
-            let col = (i % 3) + offset;
+        for (j, digit) in digits.iter().enumerate() {
+            let row = (j / shape.box_cols) + row_offset;
+            let col = (j % shape.box_cols) + col_offset;
             grid[row][col] = *digit;
         }
     }
 
-    match fill_grid(grid, rng) {
-        Some(grid) => grid,
-        None => panic!("Unable to fill grid"),
+    if fill_grid(&mut grid, shape, rng) {
+        Ok(grid)
+    } else {
+        Err(GenerationError::FillFailed)
     }
 }
 
-/// Recursively fills cells in the grid until everything is filled
-fn fill_grid(grid: Vec<Vec<u8>>, rng: &mut Pcg64) -> Option<Vec<Vec<u8>>> {
+/// Recursively fills cells in the grid until everything is filled. Digits are placed in place
+/// and reset to 0 when backtracking, so the grid is left untouched if it can't be filled.
+/// Cells are filled in reading order rather than from the most constrained one like the
+/// solvers, which would change the grid generated from a seed.
+fn fill_grid(grid: &mut Board, shape: GridShape, rng: &mut Pcg64) -> bool {
     // Find first empty cell
-    let (row_idx, col_idx) = match get_first_empty_index(&grid) {
-        Some((r, c)) => (r, c),
-        None => return Some(grid), // No empty cell means grid is fully filled
+    let (row_idx, col_idx) = match first_empty_cell(grid, shape) {
+        Some(cell) => cell,
+        None => return true, // No empty cell means grid is fully filled
     };
+    let candidates = cell_candidates(grid, shape, row_idx, col_idx);
 
-    let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let mut digits: Vec<u8> = (1..=shape.size() as u8).collect();
     digits.shuffle(rng);
 
     for digit in digits {
-        if is_safe_placement(&grid, row_idx, col_idx, digit) {
-            //let grid_copy: Vec<Vec<u8>> = grid.iter().cloned().collect();
-            let mut grid_copy: Vec<Vec<u8>> = grid.clone();
-            grid_copy[row_idx][col_idx] = digit;
-
-            if let Some(g) = fill_grid(grid_copy, rng) {
-                return Some(g); // Bubbling up filled grid
+        if candidates & digit_bit(digit) != 0 {
+            grid[row_idx][col_idx] = digit;
+            if fill_grid(grid, shape, rng) {
+                return true; // Bubbling up filled grid
             }
             // Didn't return = no solution possible with this digit, try next digit
         }
     }
 
     // No solution possible with any digits, backtrack
-    None
+    grid[row_idx][col_idx] = 0;
+    false
 }
 
-/// Counts the number of solution a grid has.
-/// Works similarily to the `fill_grid` function, but bubbles up the number
-/// of solutions instead of the filled grid
-fn solution_count(grid: Vec<Vec<u8>>) -> usize {
-    // Find first empty cell
-    let (row_idx, col_idx) = match get_first_empty_index(&grid) {
-        Some((r, c)) => (r, c),
-        None => return 1, // No empty -> grid is filled (1 solution)
+/// Counts the number of solution a grid has. Only used by tests asserting exact counts, see
+/// `has_unique_solution` which stops at the second solution.
+#[cfg(test)]
+fn solution_count<R: AsRef<[u8]>>(grid: &[R], shape: GridShape) -> usize {
+    count_solutions_up_to(&mut board_from_grid(grid), shape, usize::MAX)
+}
+
+/// Returns true if a grid (0 meaning an empty cell) has exactly one solution. Stops searching
+/// as soon as a second solution is found, unlike counting every solution.
+pub fn has_unique_solution<R: AsRef<[u8]>>(grid: &[R], shape: GridShape) -> bool {
+    // Like in `unique_solution`, the backtracking doesn't check the filled cells
+    !has_conflicts(grid, shape) && count_solutions_up_to(&mut board_from_grid(grid), shape, 2) == 1
+}
+
+/// Counts the solutions of a grid, stopping once `limit` are found. Works similarily to the
+/// `fill_grid` function, but bubbles up the number of solutions instead of stopping at the
+/// first one. The grid is restored before returning.
+fn count_solutions_up_to(grid: &mut Board, shape: GridShape, limit: usize) -> usize {
+    let (row_idx, col_idx, candidates) = match most_constrained_cell(grid, shape) {
+        Some(cell) => cell,
+        None => return 1,
     };
 
     let mut solutions = 0;
-    for digit in [1, 2, 3, 4, 5, 6, 7, 8, 9] {
-        if is_safe_placement(&grid, row_idx, col_idx, digit) {
-            let mut grid_copy = grid.clone();
-            grid_copy[row_idx][col_idx] = digit;
-            solutions += solution_count(grid_copy);
+    for digit in 1..=shape.size() as u8 {
+        if solutions >= limit {
+            break;
+        }
+        if candidates & digit_bit(digit) != 0 {
+            grid[row_idx][col_idx] = digit;
+            solutions += count_solutions_up_to(grid, shape, limit - solutions);
+        }
+    }
+    grid[row_idx][col_idx] = 0;
+    solutions
+}
+
+/// Pushes the solutions of a grid into `solutions`, until `cap` solutions are found.
+/// Works similarily to the `count_solutions_up_to` function, but keeps copies of the filled
+/// grids. The grid is restored before returning.
+fn collect_solutions(grid: &mut Board, shape: GridShape, cap: usize, solutions: &mut Vec<Board>) {
+    let (row_idx, col_idx, candidates) = match most_constrained_cell(grid, shape) {
+        Some(cell) => cell,
+        None => {
+            solutions.push(*grid);
+            return;
+        }
+    };
+
+    for digit in 1..=shape.size() as u8 {
+        if solutions.len() >= cap {
+            break;
+        }
+        if candidates & digit_bit(digit) != 0 {
+            grid[row_idx][col_idx] = digit;
+            collect_solutions(grid, shape, cap, solutions);
+        }
+    }
+    grid[row_idx][col_idx] = 0;
+}
+
+/// Returns the solution of a partially filled grid (0 meaning an empty cell), or an error if
+/// it doesn't have exactly one solution.
+pub fn unique_solution(grid: &[Vec<u8>], shape: GridShape) -> Result<Board, GenerationError> {
+    // The backtracking only validates the digits it places, make sure the filled cells don't
+    // already conflict with eachother
+    if has_conflicts(grid, shape) {
+        return Err(GenerationError::NoSolution);
+    }
+
+    // Only need to know if there's more than one solution
+    let mut solutions = Vec::new();
+    collect_solutions(&mut board_from_grid(grid), shape, 2, &mut solutions);
+    match solutions.len() {
+        0 => Err(GenerationError::NoSolution),
+        1 => Ok(solutions.remove(0)),
+        _ => Err(GenerationError::MultipleSolutions),
+    }
+}
+
+/// Returns the first solution found for a partially filled 9x9 or 6x6 grid (0 meaning an
+/// empty cell), or `None` if it has none. The grid can have multiple solutions, see
+/// `unique_solution` to make sure it doesn't.
+pub fn solve(grid: &[Vec<u8>]) -> Option<Board> {
+    let shape = GridShape::from_size(grid.len())?;
+    let mut solutions = Vec::new();
+    // Like in `unique_solution`, the backtracking doesn't check the filled cells
+    if !has_conflicts(grid, shape) {
+        collect_solutions(&mut board_from_grid(grid), shape, 1, &mut solutions);
+    }
+    solutions.pop()
+}
+
+/// Returns up to `cap` solutions of a partially filled 9x9 or 6x6 grid (0 meaning an empty
+/// cell), e.g. to show the different solutions of a hand-made puzzle that isn't unique.
+/// Returns no solution for grids of other sizes.
+pub fn solve_all(grid: &[Vec<u8>], cap: usize) -> Vec<Grid> {
+    let mut solutions = Vec::new();
+    if let Some(shape) = GridShape::from_size(grid.len()) {
+        // Like in `unique_solution`, the backtracking doesn't check the filled cells
+        if !has_conflicts(grid, shape) {
+            collect_solutions(&mut board_from_grid(grid), shape, cap, &mut solutions);
         }
     }
     solutions
+        .iter()
+        .map(|solution| board_to_grid(solution, grid.len()))
+        .collect()
+}
+
+/// Returns true if two filled cells of the grid sharing a row, column or box have the same digit
+fn has_conflicts<R: AsRef<[u8]>>(grid: &[R], shape: GridShape) -> bool {
+    // Digits already seen in every row, column and box
+    let (mut rows, mut cols, mut boxes) = ([0u16; 9], [0u16; 9], [0u16; 9]);
+    for (row, row_digits) in grid.iter().enumerate() {
+        for (col, &digit) in row_digits.as_ref().iter().enumerate() {
+            if digit == 0 {
+                continue;
+            }
+            let bit = digit_bit(digit);
+            let box_idx = shape.box_index(row, col);
+            if (rows[row] | cols[col] | boxes[box_idx]) & bit != 0 {
+                return true;
+            }
+            rows[row] |= bit;
+            cols[col] |= bit;
+            boxes[box_idx] |= bit;
+        }
+    }
+    false
+}
+
+/// Checks the invariants every generated puzzle must uphold: `solution` is a valid filled grid,
+/// every given matches it, and it is the only solution of the givens. Supports 9x9 and 6x6
+/// grids, 0 meaning an empty cell.
+pub fn verify_puzzle(givens: &[Vec<u8>], solution: &[Vec<u8>]) -> Result<(), VerifyError> {
+    let shape = GridShape::from_size(givens.len()).ok_or(VerifyError::Shape)?;
+    let size = shape.size();
+    if solution.len() != size || givens.iter().chain(solution).any(|r| r.len() != size) {
+        return Err(VerifyError::Shape);
+    }
+
+    let digits = 1..=size as u8;
+    if !solution.iter().flatten().all(|d| digits.contains(d)) || has_conflicts(solution, shape) {
+        return Err(VerifyError::InvalidSolution);
+    }
+    for (row, row_vec) in givens.iter().enumerate() {
+        for (col, &given) in row_vec.iter().enumerate() {
+            if given != 0 && given != solution[row][col] {
+                return Err(VerifyError::GivenMismatch(row, col));
+            }
+        }
+    }
+
+    // The givens match a valid solution, so there's at least one
+    unique_solution(givens, shape)?;
+    Ok(())
 }
 
-/// Masks a filled grid until `given_count` cells remain
-fn mask_grid(grid: Vec<Vec<u8>>, given_count: usize, rng: &mut Pcg64) -> Vec<Vec<u8>> {
-    // This function could reach a state where no removal actions would result in a unique
-    // situation, in which case the function would get stuck in a loop. Add a safeguard if it
-    // occurs often (doubt it should be common)
-    assert!(given_count >= 17); // Need at least 17 clues to have unique solution
-    let mut mask_count = 9 * 9 - given_count;
+/// Number of failed removals in a row after which a removal phase of `mask_grid` gives up
+const MAX_FAILED_REMOVALS: usize = 50;
+
+/// Masks a filled grid until `given_count` cells remain, or until no more cells can be removed
+/// while keeping a unique solution (unless `options.allow_non_unique` is set).
+/// Returns the masked grid.
+fn mask_grid(
+    grid: Board,
+    given_count: usize,
+    options: &GenerationOptions,
+    rng: &mut Pcg64,
+) -> Board {
+    // Need at least 17 clues (for a 9x9 grid) to have unique solution, a lower given_count
+    // can't be reached unless non-unique puzzles are allowed
+    let shape = options.shape;
+    let size = shape.size();
+    let min_clues = options.min_clues_per_box;
+    let cell_count = size * size;
+    let mut mask_count = cell_count.saturating_sub(given_count);
     let mut removed = 0;
 
-    // First 20 removals done in quads
-    let mut masked_grid = grid.clone();
-    while mask_count >= 4 && removed < 20 {
+    // Removals are done in quads, then in pairs for the first 20 and 30 removals of a 9x9 grid.
+    // Scale those thresholds for grids with a different amount of cells.
+    let quad_removals = 20 * cell_count / 81;
+    let pair_removals = 30 * cell_count / 81;
+
+    // First removals done in quads
+    let mut masked_grid = grid;
+    let mut failed_removals = 0;
+    while mask_count >= 4 && removed < quad_removals && failed_removals < MAX_FAILED_REMOVALS {
         // TODO Cells 1-4 could have some overlap with each other. Maybe validate there's no
         //  overlap if worthwhile?
-        let (c1_r, c1_c) = get_random_unmasked_cell(&masked_grid, rng);
-        let (c2_r, c2_c) = get_random_unmasked_cell(&masked_grid, rng);
-        let (c3_r, c3_c) = get_jittery_mirrored_cell(&masked_grid, c1_r, c1_c, rng);
-        let (c4_r, c4_c) = get_jittery_mirrored_cell(&masked_grid, c2_r, c2_c, rng);
+        let (c1_r, c1_c) = get_random_unmasked_cell(&masked_grid, size, rng);
+        let (c2_r, c2_c) = get_random_unmasked_cell(&masked_grid, size, rng);
+        let (c3_r, c3_c) = get_jittery_mirrored_cell(&masked_grid, size, c1_r, c1_c, rng);
+        let (c4_r, c4_c) = get_jittery_mirrored_cell(&masked_grid, size, c2_r, c2_c, rng);
 
         // Mask the cells
         masked_grid[c1_r][c1_c] = 0;
@@ -111,73 +847,279 @@ fn mask_grid(grid: Vec<Vec<u8>>, given_count: usize, rng: &mut Pcg64) -> Vec<Vec
         masked_grid[c3_r][c3_c] = 0;
         masked_grid[c4_r][c4_c] = 0;
 
-        if solution_count(masked_grid.clone()) == 1 {
+        let quad = [(c1_r, c1_c), (c2_r, c2_c), (c3_r, c3_c), (c4_r, c4_c)];
+        if keeps_min_clues(&masked_grid, shape, &quad, min_clues)
+            && has_unique_solution(&masked_grid, shape)
+        {
             mask_count -= 4;
             removed += 4;
+            failed_removals = 0;
         } else {
-            // Multiple solution with removals, restore cells and try other quad
+            // Multiple solution with removals (or box left with too few givens), restore cells
+            // and try other quad
             masked_grid[c1_r][c1_c] = grid[c1_r][c1_c];
             masked_grid[c2_r][c2_c] = grid[c2_r][c2_c];
             masked_grid[c3_r][c3_c] = grid[c3_r][c3_c];
             masked_grid[c4_r][c4_c] = grid[c4_r][c4_c];
+            failed_removals += 1;
         }
     }
 
     // Remove cells in mirrored pairs
-    while mask_count >= 2 && removed < 30 {
-        let (c1_r, c1_c) = get_random_unmasked_cell(&masked_grid, rng);
-        let (c2_r, c2_c) = get_jittery_mirrored_cell(&masked_grid, c1_r, c1_c, rng);
+    failed_removals = 0;
+    while mask_count >= 2 && removed < pair_removals && failed_removals < MAX_FAILED_REMOVALS {
+        let (c1_r, c1_c) = get_random_unmasked_cell(&masked_grid, size, rng);
+        let (c2_r, c2_c) = get_jittery_mirrored_cell(&masked_grid, size, c1_r, c1_c, rng);
 
         masked_grid[c1_r][c1_c] = 0;
         masked_grid[c2_r][c2_c] = 0;
 
-        if solution_count(masked_grid.clone()) == 1 {
+        let pair = [(c1_r, c1_c), (c2_r, c2_c)];
+        if keeps_min_clues(&masked_grid, shape, &pair, min_clues)
+            && has_unique_solution(&masked_grid, shape)
+        {
             mask_count -= 2;
             removed += 2;
+            failed_removals = 0;
         } else {
             // Puzzle has 1+ solution, restore cells and choose new ones
             masked_grid[c1_r][c1_c] = grid[c1_r][c1_c];
             masked_grid[c2_r][c2_c] = grid[c2_r][c2_c];
+            failed_removals += 1;
         }
     }
 
-    // Remove remaining cells individually
+    // Remove remaining cells individually. Every unmasked cell is tried (in a random order)
+    // before giving up, meaning we stop once no single removal keeps the solution unique.
+    // Overlapping quads may have masked fewer cells than counted, start from the actual count.
+    mask_count = get_unmasked_cells(&masked_grid)
+        .len()
+        .saturating_sub(given_count);
     while mask_count >= 1 {
-        let (cell_r, cell_c) = get_random_unmasked_cell(&masked_grid, rng);
-        masked_grid[cell_r][cell_c] = 0;
+        let mut cells = get_unmasked_cells(&masked_grid);
+        cells.shuffle(rng);
 
-        if solution_count(masked_grid.clone()) == 1 {
-            mask_count -= 1;
-        } else {
-            masked_grid[cell_r][cell_c] = grid[cell_r][cell_c];
+        let removable = cells.into_iter().find(|&(r, c)| {
+            masked_grid[r][c] = 0;
+            let is_valid = keeps_min_clues(&masked_grid, shape, &[(r, c)], min_clues)
+                && has_unique_solution(&masked_grid, shape);
+            if !is_valid {
+                masked_grid[r][c] = grid[r][c];
+            }
+            is_valid
+        });
+
+        match removable {
+            Some(_) => mask_count -= 1,
+            None => break,
+        }
+    }
+
+    // Stuck with a unique solution, keep masking without caring about uniqueness if allowed
+    if options.allow_non_unique {
+        // Overlapping quads may have masked fewer cells than counted, use the actual count
+        let mut cells = get_unmasked_cells(&masked_grid);
+        let mut remaining = cells.len().saturating_sub(given_count);
+        cells.shuffle(rng);
+        for (r, c) in cells {
+            if remaining == 0 {
+                break;
+            }
+            masked_grid[r][c] = 0;
+            if keeps_min_clues(&masked_grid, shape, &[(r, c)], min_clues) {
+                remaining -= 1;
+            } else {
+                masked_grid[r][c] = grid[r][c];
+            }
         }
     }
 
     masked_grid
 }
 
-fn get_first_empty_index(grid: &[Vec<u8>]) -> Option<(usize, usize)> {
-    let flat_index = match grid
-        .iter()
-        .flat_map(|r| r.iter())
+/// Returns the number of givens of a grid masked from `filled`, or an error holding both
+/// grids if it's above `given_count`
+fn check_given_count(
+    filled: &Board,
+    masked: &Board,
+    size: usize,
+    given_count: usize,
+) -> Result<usize, GenerationError> {
+    let reached = get_unmasked_cells(masked).len();
+    if reached > given_count {
+        return Err(GenerationError::CouldNotReachGivenCount {
+            requested: given_count,
+            reached,
+            givens: board_to_grid(masked, size),
+            solution: board_to_grid(filled, size),
+        });
+    }
+    Ok(reached)
+}
+
+/// Masks a filled grid until `given_count` cells remain, following the symmetry of `options`
+fn mask(grid: Board, given_count: usize, options: &GenerationOptions, rng: &mut Pcg64) -> Board {
+    match options.symmetry {
+        Symmetry::None => mask_grid(grid, given_count, options, rng),
+        symmetry => mask_symmetric(grid, given_count, options, symmetry, rng),
+    }
+}
+
+/// Masks cells of a filled grid in pairs of cells mirrored by `symmetry`, so that the givens
+/// keep its pattern. Every pair is tried once in a random order, and restored if masking it
+/// breaks the uniqueness of the solution, leaves a box with fewer than
+/// `options.min_clues_per_box` givens or goes below `given_count`. With
+/// `options.allow_non_unique`, the remaining pairs are then tried again without caring about
+/// uniqueness.
+fn mask_symmetric(
+    grid: Board,
+    given_count: usize,
+    options: &GenerationOptions,
+    symmetry: Symmetry,
+    rng: &mut Pcg64,
+) -> Board {
+    let shape = options.shape;
+    let size = shape.size();
+    let mut masked_grid = grid;
+    let mut given_left = size * size;
+
+    for require_unique in [true, false] {
+        if !require_unique && !options.allow_non_unique {
+            break;
+        }
+        let mut cells = get_unmasked_cells(&masked_grid);
+        cells.shuffle(rng);
+        for (r, c) in cells {
+            // Already masked as the mirror of a previous cell
+            if masked_grid[r][c] == 0 {
+                continue;
+            }
+            let (mirror_r, mirror_c) = symmetry.mirror(r, c, size);
+            let pair_size = if (mirror_r, mirror_c) == (r, c) { 1 } else { 2 };
+            if given_left < given_count + pair_size {
+                continue;
+            }
+
+            masked_grid[r][c] = 0;
+            masked_grid[mirror_r][mirror_c] = 0;
+            let pair = [(r, c), (mirror_r, mirror_c)];
+            if keeps_min_clues(&masked_grid, shape, &pair, options.min_clues_per_box)
+                && (!require_unique || has_unique_solution(&masked_grid, shape))
+            {
+                given_left -= pair_size;
+            } else {
+                masked_grid[r][c] = grid[r][c];
+                masked_grid[mirror_r][mirror_c] = grid[mirror_r][mirror_c];
+            }
+        }
+    }
+    masked_grid
+}
+
+/// Returns true if the boxes containing `cells` all have at least `min_clues` givens
+fn keeps_min_clues<R: AsRef<[u8]>>(
+    grid: &[R],
+    shape: GridShape,
+    cells: &[(usize, usize)],
+    min_clues: u8,
+) -> bool {
+    cells.iter().all(|&(row, col)| {
+        let (box_row, box_col) = shape.box_origin(row, col);
+        let givens = grid[box_row..box_row + shape.box_rows]
+            .iter()
+            .flat_map(|r| &r.as_ref()[box_col..box_col + shape.box_cols])
+            .filter(|&&digit| digit != 0)
+            .count();
+        givens >= min_clues as usize
+    })
+}
+
+fn get_unmasked_cells<R: AsRef<[u8]>>(grid: &[R]) -> Vec<(usize, usize)> {
+    grid.iter()
         .enumerate()
-        .find(|(_, &val)| val == 0)
-    {
-        Some((idx, _)) => idx,
-        None => return None,
-    };
+        .flat_map(|(r, row)| {
+            row.as_ref()
+                .iter()
+                .enumerate()
+                .filter(|&(_, &digit)| digit != 0)
+                .map(move |(c, _)| (r, c))
+        })
+        .collect()
+}
+
+/// Returns the bit of `digit` in a candidate bitmask, see `most_constrained_cell`
+fn digit_bit(digit: u8) -> u16 {
+    1 << (digit - 1)
+}
+
+/// Returns the empty cell with the fewest digits that can legally go in it, along with these
+/// digits as a bitmask (see `digit_bit`), or `None` if the grid is full. Backtracking from the
+/// most constrained cell prunes dead ends much earlier than going in reading order. Ties go to
+/// the first cell in reading order.
+fn most_constrained_cell(grid: &Board, shape: GridShape) -> Option<(usize, usize, u16)> {
+    let size = shape.size();
+    // Digits already used by every row, column and box
+    let (mut rows, mut cols, mut boxes) = ([0u16; 9], [0u16; 9], [0u16; 9]);
+    for (row, row_vec) in grid.iter().enumerate() {
+        for (col, &digit) in row_vec.iter().enumerate() {
+            if digit != 0 {
+                rows[row] |= digit_bit(digit);
+                cols[col] |= digit_bit(digit);
+                boxes[shape.box_index(row, col)] |= digit_bit(digit);
+            }
+        }
+    }
+
+    let all_digits = (1u16 << size) - 1;
+    let mut best: Option<(usize, usize, u16)> = None;
+    for row in 0..size {
+        for col in 0..size {
+            if grid[row][col] != 0 {
+                continue;
+            }
+            let used = rows[row] | cols[col] | boxes[shape.box_index(row, col)];
+            let candidates = all_digits & !used;
+            if best.is_none_or(|(_, _, b)| candidates.count_ones() < b.count_ones()) {
+                best = Some((row, col, candidates));
+                // Can't do better than a dead end or a forced digit
+                if candidates.count_ones() <= 1 {
+                    return best;
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Returns the digits that can legally go in a cell as a bitmask, see `digit_bit`
+fn cell_candidates(grid: &Board, shape: GridShape, row: usize, col: usize) -> u16 {
+    let size = shape.size();
+    let (box_row, box_col) = shape.box_origin(row, col);
+    let mut used = 0;
+    for i in 0..size {
+        let box_digit = grid[box_row + i / shape.box_cols][box_col + i % shape.box_cols];
+        for digit in [grid[row][i], grid[i][col], box_digit] {
+            if digit != 0 {
+                used |= digit_bit(digit);
+            }
+        }
+    }
+    ((1u16 << size) - 1) & !used
+}
 
-    // Convert flat index to 2d indexes
-    let row_idx = flat_index / 9;
-    let col_idx = flat_index % 9;
-    Some((row_idx, col_idx))
+/// Returns the first empty cell in reading order, or `None` if the grid is full
+fn first_empty_cell(grid: &Board, shape: GridShape) -> Option<(usize, usize)> {
+    let size = shape.size();
+    (0..size)
+        .flat_map(|row| (0..size).map(move |col| (row, col)))
+        .find(|&(row, col)| grid[row][col] == 0)
 }
 
-fn get_random_unmasked_cell(grid: &[Vec<u8>], rng: &mut Pcg64) -> (usize, usize) {
+fn get_random_unmasked_cell(grid: &Board, size: usize, rng: &mut Pcg64) -> (usize, usize) {
     // Function assumes there is at least 1 non-zero cell
     loop {
-        let row = rng.gen_range(0..9);
-        let col = rng.gen_range(0..9);
+        let row = rng.gen_range(0..size);
+        let col = rng.gen_range(0..size);
         if grid[row][col] != 0 {
             return (row, col);
         }
@@ -185,20 +1127,24 @@ fn get_random_unmasked_cell(grid: &[Vec<u8>], rng: &mut Pcg64) -> (usize, usize)
 }
 
 fn get_jittery_mirrored_cell(
-    grid: &[Vec<u8>],
+    grid: &Board,
+    size: usize,
     row: usize,
     col: usize,
     rng: &mut Pcg64,
 ) -> (usize, usize) {
-    let mirror_r = 9 - row as isize - 1;
-    let mirror_c = 9 - col as isize - 1;
+    let size = size as isize;
+    let mirror_r = size - row as isize - 1;
+    let mirror_c = size - col as isize - 1;
+    // Offsets of up to 3 cells for a 9x9 grid
+    let max_offset = size / 3;
     loop {
         // Give small offsets to mirrored position
         // Under & overflows loop around
-        let new_r = mirror_r + rng.gen_range(-3..=3);
-        let new_r = ((new_r + 9) % 9) as usize;
-        let new_c = mirror_c + rng.gen_range(-3..=3);
-        let new_c = ((new_c + 9) % 9) as usize;
+        let new_r = mirror_r + rng.gen_range(-max_offset..=max_offset);
+        let new_r = ((new_r + size) % size) as usize;
+        let new_c = mirror_c + rng.gen_range(-max_offset..=max_offset);
+        let new_c = ((new_c + size) % size) as usize;
 
         if grid[new_r][new_c] != 0 {
             return (new_r, new_c);
@@ -206,101 +1152,95 @@ fn get_jittery_mirrored_cell(
     }
 }
 
-/// Checks if grid is still valid after placing new digit in a specified cell
-fn is_safe_placement(grid: &[Vec<u8>], row: usize, col: usize, val: u8) -> bool {
-    // Check if row still valid
-    let mut seen = [false; 9];
-    seen[val as usize - 1] = true;
-    for elem in &grid[row] {
-        if *elem == 0 {
-            continue;
-        }
-        let idx = *elem as usize - 1;
-        if seen[idx] {
-            // Digit already seen before
-            return false;
-        }
-        seen[idx] = true;
-    }
-
-    // Check for col
-    let mut seen = [false; 9];
-    seen[val as usize - 1] = true;
-    for row in grid.iter() {
-        let elem = row[col];
-        if elem == 0 {
-            continue;
-        }
-        let idx = elem as usize - 1;
-        if seen[idx] {
-            return false;
-        }
-        seen[idx] = true;
-    }
-
-    // Check for box
-    let box_row = row / 3;
-    let box_col = col / 3;
-    let mut seen = [false; 9];
-    seen[val as usize - 1] = true;
-    for r in 0..3 {
-        for c in 0..3 {
-            let row_idx = 3 * box_row + r;
-            let col_idx = 3 * box_col + c;
-            let elem = grid[row_idx][col_idx];
-            if elem == 0 {
-                continue;
-            }
-            let idx = elem as usize - 1;
-            if seen[idx] {
-                return false;
-            }
-            seen[idx] = true;
-        }
-    }
-    true
-}
+/// Known sudoku grid to have a unique solution, shared by the tests of every module
+#[cfg(test)]
+pub(crate) const UNIQUE_GRID: Board = [
+    [0, 1, 0, 0, 2, 0, 3, 0, 4],
+    [0, 0, 2, 0, 0, 5, 6, 1, 0],
+    [7, 0, 0, 0, 0, 3, 0, 8, 0],
+    [5, 0, 6, 0, 4, 0, 0, 0, 1],
+    [0, 0, 1, 0, 0, 0, 2, 0, 0],
+    [9, 0, 0, 0, 7, 0, 4, 0, 5],
+    [0, 4, 0, 6, 0, 0, 0, 0, 9],
+    [0, 6, 7, 2, 0, 0, 5, 0, 0],
+    [2, 0, 8, 0, 1, 0, 0, 3, 0],
+];
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Proved to have 5 solutions by 3rd party validators
+    const MANY_SOLUTIONS_GRID: Board = [
+        [0, 0, 0, 0, 2, 0, 3, 0, 4],
+        [0, 0, 2, 0, 0, 5, 6, 1, 0],
+        [7, 0, 0, 0, 0, 3, 0, 8, 0],
+        [5, 0, 6, 0, 0, 0, 0, 0, 1],
+        [0, 0, 1, 0, 0, 0, 2, 0, 0],
+        [9, 0, 0, 0, 7, 0, 4, 0, 5],
+        [0, 4, 0, 0, 0, 0, 0, 0, 9],
+        [0, 6, 7, 0, 0, 0, 5, 0, 0],
+        [2, 0, 8, 0, 1, 0, 0, 0, 0],
+    ];
+
     #[test]
     fn test_single_solution_grid() {
-        // Known sudoku grid to have a unique solution
-        let grid: Vec<Vec<u8>> = [
-            vec![0, 1, 0, 0, 2, 0, 3, 0, 4],
-            vec![0, 0, 2, 0, 0, 5, 6, 1, 0],
-            vec![7, 0, 0, 0, 0, 3, 0, 8, 0],
-            vec![5, 0, 6, 0, 4, 0, 0, 0, 1],
-            vec![0, 0, 1, 0, 0, 0, 2, 0, 0],
-            vec![9, 0, 0, 0, 7, 0, 4, 0, 5],
-            vec![0, 4, 0, 6, 0, 0, 0, 0, 9],
-            vec![0, 6, 7, 2, 0, 0, 5, 0, 0],
-            vec![2, 0, 8, 0, 1, 0, 0, 3, 0],
-        ]
-        .to_vec();
+        let grid = board_to_grid(&UNIQUE_GRID, 9);
 
-        assert_eq!(solution_count(grid), 1);
+        assert!(has_unique_solution(&grid, GridShape::CLASSIC));
+        assert_eq!(solution_count(&grid, GridShape::CLASSIC), 1);
     }
 
     #[test]
     fn test_many_solutions_grid() {
-        // Proved to have 5 solutions by 3rd party validators
-        let grid: Vec<Vec<u8>> = [
-            vec![0, 0, 0, 0, 2, 0, 3, 0, 4],
-            vec![0, 0, 2, 0, 0, 5, 6, 1, 0],
-            vec![7, 0, 0, 0, 0, 3, 0, 8, 0],
-            vec![5, 0, 6, 0, 0, 0, 0, 0, 1],
-            vec![0, 0, 1, 0, 0, 0, 2, 0, 0],
-            vec![9, 0, 0, 0, 7, 0, 4, 0, 5],
-            vec![0, 4, 0, 0, 0, 0, 0, 0, 9],
-            vec![0, 6, 7, 0, 0, 0, 5, 0, 0],
-            vec![2, 0, 8, 0, 1, 0, 0, 0, 0],
-        ]
-        .to_vec();
+        let grid = board_to_grid(&MANY_SOLUTIONS_GRID, 9);
+
+        assert!(!has_unique_solution(&grid, GridShape::CLASSIC));
+        // The early exit stops at the limit, while full counting finds them all
+        assert_eq!(
+            count_solutions_up_to(&mut board_from_grid(&grid), GridShape::CLASSIC, 2),
+            2
+        );
+        assert_eq!(solution_count(&grid, GridShape::CLASSIC), 5);
+    }
+
+    #[test]
+    fn test_solve_all() {
+        let grid = board_to_grid(&MANY_SOLUTIONS_GRID, 9);
+
+        assert_eq!(solve_all(&grid, 3).len(), 3);
+        let solutions = solve_all(&grid, 10);
+        assert_eq!(solutions.len(), 5);
+        for (i, solution) in solutions.iter().enumerate() {
+            assert!(!solutions[i + 1..].contains(solution));
+            assert!(!has_conflicts(solution, GridShape::CLASSIC));
+            assert!(grid
+                .iter()
+                .flatten()
+                .zip(solution.iter().flatten())
+                .all(|(&given, &digit)| given == 0 || given == digit));
+        }
+
+        let mut conflicting = grid;
+        conflicting[0][0] = 2;
+        assert!(solve_all(&conflicting, 10).is_empty());
+    }
+
+    #[test]
+    fn test_solve() {
+        let (solution, givens, _) =
+            generate_grid("EXAMPLE_SEED".into(), 30, &GenerationOptions::default()).unwrap();
+        let board = board_from_grid(&solution);
+        assert_eq!(solve(&givens), Some(board));
+        assert_eq!(solve(&solution), Some(board));
 
-        assert_eq!(solution_count(grid), 5);
+        let mut contradictory = givens;
+        let (r, c) =
+            first_empty_cell(&board_from_grid(&contradictory), GridShape::CLASSIC).unwrap();
+        // Same digit as the given of another cell of the row
+        let other = contradictory[r].iter().copied().find(|&d| d != 0).unwrap();
+        contradictory[r][c] = other;
+        assert_eq!(solve(&contradictory), None);
     }
 
     #[test]
@@ -308,15 +1248,505 @@ mod tests {
         // Tests if the same seed always results in the same grid and mask
         const SEED: &str = "EXAMPLE_SEED";
         let mut rng: Pcg64 = Seeder::from(SEED).make_rng();
-        let grid_1 = generate_random_filled_grid(&mut rng);
-        let mask_1 = mask_grid(grid_1.clone(), 25, &mut rng);
+        let options = GenerationOptions::default();
+        let grid_1 = generate_random_filled_grid(GridShape::CLASSIC, &mut rng).unwrap();
+        let mask_1 = mask_grid(grid_1, 25, &options, &mut rng);
 
         // Recreate a new rng object (reset the seed)
         let mut rng: Pcg64 = Seeder::from(SEED).make_rng();
-        let grid_2 = generate_random_filled_grid(&mut rng);
-        let mask_2 = mask_grid(grid_2.clone(), 25, &mut rng);
+        let grid_2 = generate_random_filled_grid(GridShape::CLASSIC, &mut rng).unwrap();
+        let mask_2 = mask_grid(grid_2, 25, &options, &mut rng);
 
         assert_eq!(grid_1, grid_2);
         assert_eq!(mask_1, mask_2);
     }
+
+    #[test]
+    fn test_seed_stability() {
+        // Seeds are saved and shared, they must keep giving the grids of earlier versions
+        let options = GenerationOptions::default();
+        let (filled, _, _) = generate_grid("EXAMPLE_SEED".into(), 81, &options).unwrap();
+        assert_eq!(
+            filled,
+            [
+                [7, 4, 3, 5, 8, 6, 1, 2, 9],
+                [5, 8, 2, 7, 9, 1, 4, 3, 6],
+                [1, 6, 9, 2, 3, 4, 5, 7, 8],
+                [8, 9, 1, 3, 4, 5, 2, 6, 7],
+                [4, 3, 7, 6, 2, 9, 8, 5, 1],
+                [2, 5, 6, 1, 7, 8, 9, 4, 3],
+                [3, 7, 5, 9, 1, 2, 6, 8, 4],
+                [6, 1, 8, 4, 5, 3, 7, 9, 2],
+                [9, 2, 4, 8, 6, 7, 3, 1, 5],
+            ]
+        );
+
+        let options = GenerationOptions {
+            shape: GridShape::SIX,
+            ..Default::default()
+        };
+        let (filled, _, _) = generate_grid("EXAMPLE_SEED".into(), 36, &options).unwrap();
+        assert_eq!(
+            filled,
+            [
+                [2, 3, 6, 4, 5, 1],
+                [5, 1, 4, 2, 3, 6],
+                [6, 5, 2, 1, 4, 3],
+                [1, 4, 3, 5, 6, 2],
+                [3, 2, 5, 6, 1, 4],
+                [4, 6, 1, 3, 2, 5],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_grid_reproducible() {
+        // The seed is the only source of randomness, same seed means same puzzle
+        let options = GenerationOptions::default();
+        let first = generate_grid("hello".into(), 30, &options).unwrap();
+        assert_eq!(
+            generate_grid("hello".into(), 30, &options),
+            Ok(first.clone())
+        );
+        assert_ne!(
+            generate_grid("world".into(), 30, &options).unwrap().0,
+            first.0
+        );
+    }
+
+    #[test]
+    fn test_six_by_six_grid() {
+        let shape = GridShape::SIX;
+        let options = GenerationOptions {
+            shape,
+            ..Default::default()
+        };
+        let (filled, masked, _) = generate_grid("EXAMPLE_SEED".into(), 14, &options).unwrap();
+
+        assert_eq!(filled.len(), 6);
+        assert!(filled.iter().all(|r| r.len() == 6));
+        // Every cell of the filled grid must be valid in regards to its 2x3 box
+        assert!(filled.iter().flatten().all(|d| (1..=6).contains(d)));
+        assert!(!has_conflicts(&filled, shape));
+
+        assert_eq!(masked.iter().flatten().filter(|&&d| d != 0).count(), 14);
+        assert_eq!(solution_count(&masked, shape), 1);
+    }
+
+    #[test]
+    fn test_unreachable_given_count() {
+        // No 6x6 puzzle with 4 givens has a unique solution, generation stops where it's stuck
+        let mut options = GenerationOptions {
+            shape: GridShape::SIX,
+            ..Default::default()
+        };
+        match generate_grid("EXAMPLE_SEED".into(), 4, &options) {
+            Err(GenerationError::CouldNotReachGivenCount {
+                requested, reached, ..
+            }) => {
+                assert_eq!(requested, 4);
+                assert!(reached >= GridShape::SIX.min_givens());
+            }
+            result => panic!("Expected CouldNotReachGivenCount, got {result:?}"),
+        }
+
+        // Unless uniqueness isn't required
+        options.allow_non_unique = true;
+        let (_, _, count) = generate_grid("EXAMPLE_SEED".into(), 4, &options).unwrap();
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_masking_always_terminates() {
+        // Requesting the minimum given count of a 6x6 grid is unreachable for most filled
+        // grids. Masking must give up, leaving a grid with a unique solution.
+        let options = GenerationOptions {
+            shape: GridShape::SIX,
+            ..Default::default()
+        };
+        let min_givens = GridShape::SIX.min_givens();
+        let mut unreached = 0;
+        for i in 0..20 {
+            let mut rng: Pcg64 = Seeder::from(format!("SEED_{i}")).make_rng();
+            let filled = generate_random_filled_grid(options.shape, &mut rng).unwrap();
+            let masked = mask_grid(filled, min_givens, &options, &mut rng);
+            assert_eq!(solution_count(&masked, GridShape::SIX), 1);
+            if get_unmasked_cells(&masked).len() > min_givens {
+                unreached += 1;
+            }
+        }
+        assert!(unreached > 0);
+    }
+
+    #[test]
+    fn test_could_not_reach_given_count() {
+        // 17 givens is the minimum of a unique 9x9 puzzle, masking gets stuck before that
+        for i in 0..5 {
+            match generate_grid(format!("SEED_{i}"), 17, &GenerationOptions::default()) {
+                Err(GenerationError::CouldNotReachGivenCount {
+                    requested, reached, ..
+                }) => {
+                    assert_eq!(requested, 17);
+                    assert!(reached > 17);
+                }
+                result => panic!("Expected CouldNotReachGivenCount, got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_puzzle_bytes_round_trip() {
+        let (solution, givens, _) =
+            generate_grid("EXAMPLE_SEED".into(), 30, &GenerationOptions::default()).unwrap();
+        let puzzle = Puzzle {
+            givens,
+            solution,
+            difficulty: 3,
+        };
+
+        let bytes = puzzle.to_bytes();
+        assert_eq!(bytes.len(), Puzzle::BYTE_LEN);
+        let decoded = Puzzle::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, puzzle);
+        assert_eq!(solution_count(&decoded.givens, GridShape::CLASSIC), 1);
+
+        // Corrupted puzzles are rejected
+        assert_eq!(
+            Puzzle::from_bytes(&bytes[1..]),
+            Err(PuzzleDecodeError::Length(82))
+        );
+        let mut invalid_digit = bytes.clone();
+        invalid_digit[1] = 0xA0;
+        assert_eq!(
+            Puzzle::from_bytes(&invalid_digit),
+            Err(PuzzleDecodeError::Digit(0, 10))
+        );
+        let mut invalid_solution = bytes.clone();
+        invalid_solution[Puzzle::BYTE_LEN - 1] = 0;
+        assert_eq!(
+            Puzzle::from_bytes(&invalid_solution),
+            Err(PuzzleDecodeError::InvalidSolution)
+        );
+        // Swapping the first two digits of the solution breaks its columns
+        let mut swapped = bytes;
+        swapped[42] = swapped[42].rotate_left(4);
+        assert_eq!(
+            Puzzle::from_bytes(&swapped),
+            Err(PuzzleDecodeError::InvalidSolution)
+        );
+    }
+
+    #[test]
+    fn test_min_clues_per_box() {
+        let shape = GridShape::SIX;
+        let mut options = GenerationOptions {
+            shape,
+            min_clues_per_box: 2,
+            ..Default::default()
+        };
+        // Checking the top-left cell of every box
+        let box_cells: Vec<(usize, usize)> = (0..6).map(|b| (b / 2 * 2, b % 2 * 3)).collect();
+
+        let (_, masked, _) = generate_grid("EXAMPLE_SEED".into(), 14, &options).unwrap();
+        assert!(keeps_min_clues(&masked, shape, &box_cells, 2));
+        // 2 givens in each of the 6 boxes can't be masked down to 8 givens
+        assert!(matches!(
+            generate_grid("EXAMPLE_SEED".into(), 8, &options),
+            Err(GenerationError::CouldNotReachGivenCount { requested: 8, reached, .. }) if reached >= 12
+        ));
+
+        // Also respected when uniqueness isn't required
+        options.allow_non_unique = true;
+        let (_, masked, count) = generate_grid("EXAMPLE_SEED".into(), 12, &options).unwrap();
+        assert!(keeps_min_clues(&masked, shape, &box_cells, 2));
+        assert_eq!(count, 12);
+    }
+
+    #[test]
+    fn test_generate_batch() {
+        let puzzles = generate_batch("EXAMPLE_SEED".into(), 3, 30).unwrap();
+        assert_eq!(puzzles.len(), 3);
+        for (i, puzzle) in puzzles.iter().enumerate() {
+            assert!(puzzles[i + 1..].iter().all(|p| p.givens != puzzle.givens));
+            assert_eq!(
+                unique_solution(&puzzle.givens, GridShape::CLASSIC),
+                Ok(board_from_grid(&puzzle.solution))
+            );
+        }
+
+        // Same seed, same puzzles
+        let bank = generate_puzzle_bank("EXAMPLE_SEED".into(), 3, 30).unwrap();
+        assert_eq!(bank.len(), 3 * Puzzle::BYTE_LEN);
+        assert_eq!(
+            Puzzle::from_bytes(&bank[Puzzle::BYTE_LEN..2 * Puzzle::BYTE_LEN]),
+            Ok(puzzles[1].clone())
+        );
+    }
+
+    #[test]
+    fn test_verify_puzzle() {
+        let cases = [
+            (GridShape::CLASSIC, 25),
+            (GridShape::CLASSIC, 35),
+            (GridShape::CLASSIC, 50),
+        ]
+        .into_iter()
+        .chain([(GridShape::SIX, 12), (GridShape::SIX, 20)]);
+        for (shape, given_count) in cases {
+            let options = GenerationOptions {
+                shape,
+                ..Default::default()
+            };
+            let (solution, givens, count) =
+                generate_grid("EXAMPLE_SEED".into(), given_count, &options).unwrap();
+            assert_eq!(verify_puzzle(&givens, &solution), Ok(()));
+            assert_eq!(get_unmasked_cells(&givens).len(), count);
+            assert_eq!(count, given_count);
+        }
+
+        let (solution, givens, _) =
+            generate_grid("EXAMPLE_SEED".into(), 30, &GenerationOptions::default()).unwrap();
+        let (row, col) = get_unmasked_cells(&givens)[0];
+        let mut mismatch = givens.clone();
+        mismatch[row][col] = solution[row][col] % 9 + 1;
+        assert_eq!(
+            verify_puzzle(&mismatch, &solution),
+            Err(VerifyError::GivenMismatch(row, col))
+        );
+
+        let mut invalid = solution.clone();
+        invalid[0].swap(0, 1);
+        assert_eq!(
+            verify_puzzle(&givens, &invalid),
+            Err(VerifyError::InvalidSolution)
+        );
+
+        let empty = vec![vec![0; 9]; 9];
+        assert_eq!(
+            verify_puzzle(&empty, &solution),
+            Err(VerifyError::NotUnique(GenerationError::MultipleSolutions))
+        );
+        assert_eq!(
+            verify_puzzle(&empty[..8], &solution),
+            Err(VerifyError::Shape)
+        );
+    }
+
+    #[test]
+    fn test_daily_puzzle() {
+        let puzzle = daily_puzzle("2024-06-01", 30).unwrap();
+        assert_eq!(daily_puzzle("2024-06-01", 30), Ok(puzzle.clone()));
+        assert_ne!(
+            daily_puzzle("2024-06-02", 30).unwrap().givens,
+            puzzle.givens
+        );
+        assert_eq!(verify_puzzle(&puzzle.givens, &puzzle.solution), Ok(()));
+        assert_eq!(
+            Puzzle::from_bytes(&daily_puzzle_bytes("2024-06-01", 30).unwrap()),
+            Ok(puzzle)
+        );
+    }
+
+    #[test]
+    fn test_mask_existing() {
+        let (solved, _, _) =
+            generate_grid("EXAMPLE_SEED".into(), 81, &GenerationOptions::default()).unwrap();
+
+        for symmetry in [Symmetry::None, Symmetry::Central, Symmetry::Vertical] {
+            let masked = mask_existing(&solved, 31, 42, symmetry).unwrap();
+            assert_eq!(mask_existing(&solved, 31, 42, symmetry), Ok(masked.clone()));
+            assert_eq!(verify_puzzle(&masked, &solved), Ok(()));
+            assert_eq!(get_unmasked_cells(&masked).len(), 31);
+            if symmetry != Symmetry::None {
+                for (r, c) in get_unmasked_cells(&masked) {
+                    let (mirror_r, mirror_c) = symmetry.mirror(r, c, 9);
+                    assert_ne!(masked[mirror_r][mirror_c], 0);
+                }
+            }
+        }
+
+        assert!(matches!(
+            mask_existing(&solved, 17, 42, Symmetry::None),
+            Err(GenerationError::CouldNotReachGivenCount { requested: 17, .. })
+        ));
+
+        let mut invalid = solved.clone();
+        invalid[0][0] = 0;
+        assert_eq!(
+            mask_existing(&invalid, 30, 42, Symmetry::None),
+            Err(GenerationError::InvalidGrid)
+        );
+        let mut invalid = solved.clone();
+        invalid[0].swap(0, 1);
+        assert_eq!(
+            mask_existing(&invalid, 30, 42, Symmetry::None),
+            Err(GenerationError::InvalidGrid)
+        );
+        let flat: Vec<u8> = solved.iter().flatten().copied().collect();
+        assert_eq!(
+            mask_existing_flat(&flat, 31, 42, Symmetry::Central),
+            Ok(mask_existing(&solved, 31, 42, Symmetry::Central)
+                .unwrap()
+                .concat())
+        );
+    }
+
+    #[test]
+    fn test_generate_grid_with_difficulty() {
+        let seed = "EXAMPLE_SEED";
+        let (solution, easy, easy_count) =
+            generate_grid_with_difficulty(seed.into(), Difficulty::Easy).unwrap();
+        let (_, expert, expert_count) =
+            generate_grid_with_difficulty(seed.into(), Difficulty::Expert).unwrap();
+        assert!(easy_count > expert_count);
+        assert!(Difficulty::Easy.given_range().contains(&easy_count));
+        assert!(Difficulty::Expert.given_range().contains(&expert_count));
+        assert_eq!(get_unmasked_cells(&expert).len(), expert_count);
+
+        assert_eq!(
+            unique_solution(&easy, GridShape::CLASSIC),
+            Ok(board_from_grid(&solution))
+        );
+        assert!(unique_solution(&expert, GridShape::CLASSIC).is_ok());
+        assert!(!solver::solves_with_naked_singles(
+            &expert,
+            GridShape::CLASSIC
+        ));
+    }
+
+    #[test]
+    fn test_generate_symmetric_grid() {
+        for symmetry in [Symmetry::Central, Symmetry::Horizontal, Symmetry::Vertical] {
+            let options = GenerationOptions {
+                symmetry,
+                ..Default::default()
+            };
+            let (solution, givens, count) =
+                generate_grid("EXAMPLE_SEED".into(), 30, &options).unwrap();
+            assert!(count >= 30);
+            assert_eq!(
+                unique_solution(&givens, GridShape::CLASSIC),
+                Ok(board_from_grid(&solution))
+            );
+            for (r, c) in (0..9).flat_map(|r| (0..9).map(move |c| (r, c))) {
+                let (mirror_r, mirror_c) = symmetry.mirror(r, c, 9);
+                assert_eq!(givens[r][c] == 0, givens[mirror_r][mirror_c] == 0);
+            }
+        }
+        assert_eq!(Symmetry::Central.mirror(1, 2, 9), (7, 6));
+
+        // Symmetry combines with the other masking options
+        let options = GenerationOptions {
+            symmetry: Symmetry::Central,
+            min_clues_per_box: 3,
+            ..Default::default()
+        };
+        let (_, givens, _) = generate_grid("EXAMPLE_SEED".into(), 31, &options).unwrap();
+        let cells: Vec<_> = (0..9).flat_map(|r| (0..9).map(move |c| (r, c))).collect();
+        assert!(keeps_min_clues(&givens, GridShape::CLASSIC, &cells, 3));
+        let options = GenerationOptions {
+            symmetry: Symmetry::Central,
+            allow_non_unique: true,
+            ..Default::default()
+        };
+        // Below 17 givens, which no puzzle with a unique solution has
+        let (solution, givens, count) = generate_grid("EXAMPLE_SEED".into(), 16, &options).unwrap();
+        assert_eq!(count, 16);
+        assert!(givens
+            .iter()
+            .flatten()
+            .zip(solution.iter().flatten())
+            .all(|(&given, &digit)| given == 0 || given == digit));
+    }
+
+    #[test]
+    fn test_most_constrained_cell() {
+        let mut grid = [[0; 9]; 9];
+        assert_eq!(
+            most_constrained_cell(&grid, GridShape::CLASSIC),
+            Some((0, 0, 0x1ff))
+        );
+
+        // Only 9 can go in the last cell of the first row
+        grid[0][..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            most_constrained_cell(&grid, GridShape::CLASSIC),
+            Some((0, 8, digit_bit(9)))
+        );
+
+        let solution = generate_grid("EXAMPLE_SEED".into(), 30, &GenerationOptions::default())
+            .unwrap()
+            .0;
+        assert_eq!(
+            most_constrained_cell(&board_from_grid(&solution), GridShape::CLASSIC),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fill_grid_valid() {
+        for (shape, seed) in [(GridShape::CLASSIC, "FILL"), (GridShape::SIX, "FILL_SIX")] {
+            let mut rng: Pcg64 = Seeder::from(seed).make_rng();
+            for _ in 0..10 {
+                let board = generate_random_filled_grid(shape, &mut rng).unwrap();
+                let grid = board_to_grid(&board, shape.size());
+                assert!(grid.iter().flatten().all(|&d| d != 0));
+                assert!(!has_conflicts(&grid, shape));
+                // Cells outside of smaller grids stay empty
+                assert_eq!(board_from_grid(&grid), board);
+            }
+        }
+    }
+
+    #[test]
+    fn test_backtracking_restores_grid() {
+        let board = MANY_SOLUTIONS_GRID;
+        let mut scratch = board;
+        assert_eq!(
+            count_solutions_up_to(&mut scratch, GridShape::CLASSIC, 3),
+            3
+        );
+        assert_eq!(scratch, board);
+        let mut solutions = Vec::new();
+        collect_solutions(&mut scratch, GridShape::CLASSIC, 2, &mut solutions);
+        assert_eq!(scratch, board);
+
+        // Generation stays valid over many seeds
+        for i in 0..50 {
+            let (solution, givens, _) =
+                generate_grid(format!("SEED_{i}"), 30, &GenerationOptions::default()).unwrap();
+            assert_eq!(verify_puzzle(&givens, &solution), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_board_flat_round_trip() {
+        for (shape, seed) in [(GridShape::CLASSIC, "BOARD"), (GridShape::SIX, "BOARD_SIX")] {
+            let size = shape.size();
+            let mut rng: Pcg64 = Seeder::from(seed).make_rng();
+            let board = generate_random_filled_grid(shape, &mut rng).unwrap();
+
+            let flat = board_to_flat(&board, size);
+            assert_eq!(flat.len(), size * size);
+            let rows: Vec<&[u8]> = flat.chunks(size).collect();
+            assert_eq!(board_from_grid(&rows), board);
+            assert_eq!(board_from_grid(&board_to_grid(&board, size)), board);
+        }
+    }
+
+    #[test]
+    fn test_peers() {
+        let peers = GridShape::CLASSIC.peers(4, 1);
+        assert_eq!(peers.len(), 20);
+        let unique: HashSet<_> = peers.iter().collect();
+        assert_eq!(unique.len(), 20);
+        assert!(!peers.contains(&(4, 1)));
+        for (r, c) in peers {
+            let same_box = r / 3 == 1 && c / 3 == 0;
+            assert!(r == 4 || c == 1 || same_box);
+        }
+
+        // 5 in the row, 5 in the column and the 2 other cells of its 2x3 box
+        assert_eq!(GridShape::SIX.peers(0, 0).len(), 12);
+    }
 }
@@ -1,6 +1,8 @@
+use serde::Serialize;
+use std::fmt;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum Key {
     A,
     B,
@@ -40,12 +42,44 @@ pub enum Key {
     Nine,
     Space,
 
+    // Numpad digits, only told apart from the top row when parsed with `try_from_js_code`
+    NumpadOne,
+    NumpadTwo,
+    NumpadThree,
+    NumpadFour,
+    NumpadFive,
+    NumpadSix,
+    NumpadSeven,
+    NumpadEight,
+    NumpadNine,
+
     // Arrows
     ArrowUp,
     ArrowDown,
     ArrowLeft,
     ArrowRight,
 
+    // Navigation
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+
+    // Function keys
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+
     // Modifier keys
     Escape,
     Enter,
@@ -105,6 +139,23 @@ impl Key {
             "ArrowDown" => Ok(Self::ArrowDown),
             "ArrowLeft" => Ok(Self::ArrowLeft),
             "ArrowRight" => Ok(Self::ArrowRight),
+            "Delete" => Ok(Self::Delete),
+            "Home" => Ok(Self::Home),
+            "End" => Ok(Self::End),
+            "PageUp" => Ok(Self::PageUp),
+            "PageDown" => Ok(Self::PageDown),
+            "F1" => Ok(Self::F1),
+            "F2" => Ok(Self::F2),
+            "F3" => Ok(Self::F3),
+            "F4" => Ok(Self::F4),
+            "F5" => Ok(Self::F5),
+            "F6" => Ok(Self::F6),
+            "F7" => Ok(Self::F7),
+            "F8" => Ok(Self::F8),
+            "F9" => Ok(Self::F9),
+            "F10" => Ok(Self::F10),
+            "F11" => Ok(Self::F11),
+            "F12" => Ok(Self::F12),
             "Escape" => Ok(Self::Escape),
             "Enter" => Ok(Self::Enter),
             "Backspace" => Ok(Self::Backspace),
@@ -119,6 +170,39 @@ impl Key {
         }
     }
 
+    /// Converts from the value of KeyboardEvent.code, which names the physical key rather than
+    /// the character it types. Unlike `try_from_js`, this tells numpad digits apart from the
+    /// top row digits, and isn't affected by Shift.
+    pub fn try_from_js_code(value: String) -> Result<Self, KeyParseError> {
+        if let Some(digit) = value.strip_prefix("Numpad") {
+            return match digit {
+                "1" => Ok(Self::NumpadOne),
+                "2" => Ok(Self::NumpadTwo),
+                "3" => Ok(Self::NumpadThree),
+                "4" => Ok(Self::NumpadFour),
+                "5" => Ok(Self::NumpadFive),
+                "6" => Ok(Self::NumpadSix),
+                "7" => Ok(Self::NumpadSeven),
+                "8" => Ok(Self::NumpadEight),
+                "9" => Ok(Self::NumpadNine),
+                _ => Err(KeyParseError::Code(value)),
+            };
+        }
+
+        // Digit1 and KeyA name the key typing "1" and "a" on a QWERTY layout
+        let key = value
+            .strip_prefix("Digit")
+            .or_else(|| value.strip_prefix("Key"))
+            .filter(|name| name.len() == 1);
+        match (key, value.as_ref()) {
+            (Some(name), _) => Self::try_from_js(name.to_owned()),
+            (None, "Space") => Ok(Self::Space),
+            // Other keys share their code with their JS key name
+            (None, _) => Self::try_from_js(value.clone()),
+        }
+        .map_err(|_| KeyParseError::Code(value))
+    }
+
     pub fn try_from_config(value: String) -> Result<Self, KeyParseError> {
         // Most of the config keys share their name with the JS notation. Only program keys w/
         // different names, and default to JS names otherwise
@@ -128,6 +212,15 @@ impl Key {
             "Left" => Ok(Self::ArrowLeft),
             "Right" => Ok(Self::ArrowRight),
             "Space" => Ok(Self::Space),
+            "Numpad1" => Ok(Self::NumpadOne),
+            "Numpad2" => Ok(Self::NumpadTwo),
+            "Numpad3" => Ok(Self::NumpadThree),
+            "Numpad4" => Ok(Self::NumpadFour),
+            "Numpad5" => Ok(Self::NumpadFive),
+            "Numpad6" => Ok(Self::NumpadSix),
+            "Numpad7" => Ok(Self::NumpadSeven),
+            "Numpad8" => Ok(Self::NumpadEight),
+            "Numpad9" => Ok(Self::NumpadNine),
             _ => {
                 // Default to JS
                 match Self::try_from_js(value.clone()) {
@@ -151,6 +244,30 @@ impl Key {
                 | Self::Seven
                 | Self::Eight
                 | Self::Nine
+                | Self::NumpadOne
+                | Self::NumpadTwo
+                | Self::NumpadThree
+                | Self::NumpadFour
+                | Self::NumpadFive
+                | Self::NumpadSix
+                | Self::NumpadSeven
+                | Self::NumpadEight
+                | Self::NumpadNine
+        )
+    }
+
+    pub fn is_numpad(&self) -> bool {
+        matches!(
+            self,
+            Self::NumpadOne
+                | Self::NumpadTwo
+                | Self::NumpadThree
+                | Self::NumpadFour
+                | Self::NumpadFive
+                | Self::NumpadSix
+                | Self::NumpadSeven
+                | Self::NumpadEight
+                | Self::NumpadNine
         )
     }
 
@@ -166,6 +283,30 @@ impl Key {
     }
 }
 
+impl fmt::Display for Key {
+    /// Writes the key in config notation, as read by `try_from_config`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ArrowUp => write!(f, "Up"),
+            Self::ArrowDown => write!(f, "Down"),
+            Self::ArrowLeft => write!(f, "Left"),
+            Self::ArrowRight => write!(f, "Right"),
+            Self::Space => write!(f, "Space"),
+            key if key.is_numpad() => write!(f, "Numpad{}", u8::from(*key)),
+            key if key.is_digit() => write!(f, "{}", u8::from(*key)),
+            key => {
+                // Other variants are named after their JS notation, letters being lowercase
+                let name = format!("{key:?}");
+                if name.len() == 1 {
+                    write!(f, "{}", name.to_lowercase())
+                } else {
+                    write!(f, "{name}")
+                }
+            }
+        }
+    }
+}
+
 impl From<Key> for u8 {
     fn from(value: Key) -> Self {
         match value {
@@ -179,6 +320,15 @@ impl From<Key> for u8 {
             Key::Seven => 7,
             Key::Eight => 8,
             Key::Nine => 9,
+            Key::NumpadOne => 1,
+            Key::NumpadTwo => 2,
+            Key::NumpadThree => 3,
+            Key::NumpadFour => 4,
+            Key::NumpadFive => 5,
+            Key::NumpadSix => 6,
+            Key::NumpadSeven => 7,
+            Key::NumpadEight => 8,
+            Key::NumpadNine => 9,
             _ => panic!("Could not convert non-digit Key into u8."),
         }
     }
@@ -195,4 +345,107 @@ pub enum KeyParseError {
     Config(String),
     #[error("Invalid config JS notation: {0}")]
     Js(String),
+    #[error("Invalid JS key code: {0}")]
+    Code(String),
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<wasm_bindgen::JsValue> for KeyParseError {
+    fn into(self) -> wasm_bindgen::JsValue {
+        self.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_special_keys() {
+        // Keys without a config-specific name fall back to their JS name
+        assert_eq!(
+            Key::try_from_config("ContextMenu".into()).unwrap(),
+            Key::ContextMenu
+        );
+        assert_eq!(
+            Key::try_from_config("CapsLock".into()).unwrap(),
+            Key::CapsLock
+        );
+        assert!(matches!(
+            Key::try_from_config("Capslock".into()),
+            Err(KeyParseError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_display_config_notation() {
+        let keys = [
+            (Key::X, "x"),
+            (Key::Seven, "7"),
+            (Key::Space, "Space"),
+            (Key::ArrowUp, "Up"),
+            (Key::Backspace, "Backspace"),
+            (Key::ContextMenu, "ContextMenu"),
+        ];
+        for (key, name) in keys {
+            assert_eq!(key.to_string(), name);
+            assert_eq!(Key::try_from_config(key.to_string()).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_navigation_keys() {
+        let keys = [
+            ("Delete", Key::Delete),
+            ("Home", Key::Home),
+            ("End", Key::End),
+            ("PageUp", Key::PageUp),
+            ("PageDown", Key::PageDown),
+        ];
+        for (name, key) in keys {
+            assert_eq!(Key::try_from_js(name.into()).unwrap(), key);
+            assert_eq!(Key::try_from_config(name.into()).unwrap(), key);
+            assert!(!key.is_modifier() && !key.is_digit());
+        }
+    }
+
+    #[test]
+    fn test_js_code() {
+        let code = |c: &str| Key::try_from_js_code(c.into()).unwrap();
+        assert_eq!(code("Numpad1"), Key::NumpadOne);
+        assert_eq!(code("Numpad9"), Key::NumpadNine);
+        assert_eq!(code("Digit1"), Key::One);
+        assert_eq!(code("KeyX"), Key::X);
+        assert_eq!(code("Space"), Key::Space);
+        assert_eq!(code("ArrowUp"), Key::ArrowUp);
+        assert!(matches!(
+            Key::try_from_js_code("NumpadAdd".into()),
+            Err(KeyParseError::Code(_))
+        ));
+        assert!(matches!(
+            Key::try_from_js_code("Digit".into()),
+            Err(KeyParseError::Code(_))
+        ));
+
+        assert!(Key::NumpadFive.is_digit());
+        assert_eq!(u8::from(Key::NumpadFive), 5);
+        assert_eq!(Key::NumpadFive.to_string(), "Numpad5");
+        assert_eq!(
+            Key::try_from_config("Numpad5".into()).unwrap(),
+            Key::NumpadFive
+        );
+    }
+
+    #[test]
+    fn test_function_keys() {
+        for (name, key) in [("F1", Key::F1), ("F10", Key::F10), ("F12", Key::F12)] {
+            assert_eq!(Key::try_from_js(name.into()).unwrap(), key);
+            assert_eq!(Key::try_from_config(name.into()).unwrap(), key);
+            assert_eq!(Key::try_from_js_code(name.into()).unwrap(), key);
+            assert_eq!(key.to_string(), name);
+            assert!(!key.is_digit() && !key.is_arrow() && !key.is_modifier());
+        }
+        assert!(Key::try_from_js("F13".into()).is_err());
+    }
 }
@@ -1,4 +1,5 @@
-use std::{collections::HashMap, hash::Hash};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, hash::Hash};
 use thiserror::Error;
 use wasm_bindgen::prelude::*;
 
@@ -7,7 +8,7 @@ use crate::{
     key::{self, Key},
 };
 
-#[derive(Debug, Eq, Clone, Copy)]
+#[derive(Debug, Eq, Clone, Copy, Serialize)]
 pub struct Keybind {
     pub key: Key,
     pub modifier: Option<Key>,
@@ -46,6 +47,21 @@ impl Hash for Keybind {
     }
 }
 
+impl fmt::Display for Keybind {
+    /// Writes the keybind in the vim-like notation read by `try_from`, e.g. `S-1` or `x`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.modifier {
+            Some(Key::Shift) => "S-",
+            Some(Key::Control) => "C-",
+            Some(Key::Alt) => "A-",
+            Some(Key::Meta) => "M-",
+            // Only modifier keys can be parsed as modifiers
+            _ => "",
+        };
+        write!(f, "{prefix}{}", self.key)
+    }
+}
+
 impl TryFrom<String> for Keybind {
     type Error = KeybindParsingError;
 
@@ -113,6 +129,8 @@ pub enum KeybindManagerError {
     KeybindParsingError(#[from] KeybindParsingError),
     #[error("Wrongly formatted line: {0}")]
     Format(String),
+    #[error("Keybind bound more than once: {0}")]
+    DuplicateBind(String),
 }
 
 // Allow since we only ever need to send this error type to JS, never receive it from JS
@@ -133,11 +151,61 @@ pub enum KeybindParsingError {
     Format(String),
 }
 
+/// Keybinds used by `KeybindManager::with_defaults`, also showing the config syntax
+const DEFAULT_CONFIG: &str = "// Movement, vim style or with the arrows
+h = moveCol(-1, true)
+j = moveRow(1, true)
+k = moveRow(-1, true)
+l = moveCol(1, true)
+Left = moveCol(-1, true)
+Down = moveRow(1, true)
+Up = moveRow(-1, true)
+Right = moveCol(1, true)
+// Jump over filled cells
+w = nextEmpty()
+b = prevEmpty()
+S-h = moveSkip(left)
+S-j = moveSkip(down)
+S-k = moveSkip(up)
+S-l = moveSkip(right)
+// Digits, and candidates with Shift
+1 = writeCell(1)
+2 = writeCell(2)
+3 = writeCell(3)
+4 = writeCell(4)
+5 = writeCell(5)
+6 = writeCell(6)
+7 = writeCell(7)
+8 = writeCell(8)
+9 = writeCell(9)
+S-1 = toggleCandidate(1)
+S-2 = toggleCandidate(2)
+S-3 = toggleCandidate(3)
+S-4 = toggleCandidate(4)
+S-5 = toggleCandidate(5)
+S-6 = toggleCandidate(6)
+S-7 = toggleCandidate(7)
+S-8 = toggleCandidate(8)
+S-9 = toggleCandidate(9)
+x = clearCell()
+Backspace = clearCell()
+Delete = clearCell()
+c = clearCandidates()
+// Highlighting and colors
+Space = highlightCurrentDigit()
+Escape = clearAll()
+a = cycleColor()
+f = toggleFlag()
+i = toggleInputMode()
+// History
+u = undo()
+C-r = redo()";
+
 #[wasm_bindgen]
 #[derive(Clone)]
 pub struct KeybindManager {
-    // TODO replace value with resulting action type
-    binds: HashMap<Keybind, Action>,
+    /// Actions of each keybind, applied in order
+    binds: HashMap<Keybind, Vec<Action>>,
 }
 
 // Methods exported to JS
@@ -149,10 +217,13 @@ impl KeybindManager {
         for line in config.lines() {
             // Lines should have the following format:
             // Key = <action> or Mod-key = <action>
+            // Several actions can be bound to a key, separated by semicolons:
+            // Key = <action>; <action>
             // Case and spaces are ignored
 
-            // Skip comment lines
-            if line.starts_with("//") {
+            // Skip blank and comment lines, comments starting with either // or #
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with("//") || trimmed.starts_with('#') {
                 continue;
             }
 
@@ -167,18 +238,206 @@ impl KeybindManager {
                 .unwrap()
                 .try_into()
                 .map_err(KeybindManagerError::KeybindParsingError)?;
-            let action: Action = action
+            let actions = action
                 .unwrap()
-                .try_into()
+                .split(';')
+                .map(|action| Action::try_from(action.trim().to_owned()))
+                .collect::<Result<Vec<Action>, _>>()
                 .map_err(KeybindManagerError::ActionParsingError)?;
-            binds.insert(bind, action);
+            // Alt and Meta binds of the same key are duplicates too, see `Keybind::eq`
+            if binds.insert(bind, actions).is_some() {
+                return Err(KeybindManagerError::DuplicateBind(bind.to_string()));
+            }
         }
         Ok(KeybindManager { binds })
     }
+
+    /// Generates a `KeybindManager` from the built-in config, see `default_config`
+    pub fn with_defaults() -> Result<KeybindManager, KeybindManagerError> {
+        Self::with_config(Self::default_config())
+    }
+
+    /// Number of keybinds in the config
+    pub fn len(&self) -> usize {
+        self.binds.len()
+    }
+
+    /// Returns true if the config has no keybinds (e.g. it only contains comments), meaning the
+    /// game won't respond to any key
+    pub fn is_empty(&self) -> bool {
+        self.binds.is_empty()
+    }
+
+    /// Returns the keybinds as a JSON array of `[keybind, actions]` pairs, see `list_binds`
+    pub fn list_binds_json(&self) -> String {
+        serde_json::to_string(&self.list_binds()).unwrap()
+    }
 }
 
 impl KeybindManager {
-    pub fn get_action(&self, keybind: &Keybind) -> Option<&Action> {
-        self.binds.get(keybind)
+    /// Built-in config, with vim-like movement, digits on 1-9 and candidates on Shift+1-9.
+    /// Lines can be copied as a starting point for a custom config.
+    pub fn default_config() -> &'static str {
+        DEFAULT_CONFIG
+    }
+
+    /// Returns every keybind along with its actions in config notation, sorted by keybind, e.g.
+    /// to show them in a help overlay. Actions bound to the same key are separated by
+    /// semicolons, so each pair can be copied back into a config as `keybind = actions`.
+    pub fn list_binds(&self) -> Vec<(String, String)> {
+        let mut binds: Vec<(String, String)> = self
+            .binds
+            .iter()
+            .map(|(keybind, actions)| {
+                let actions: Vec<String> = actions.iter().map(Action::to_string).collect();
+                (keybind.to_string(), actions.join("; "))
+            })
+            .collect();
+        binds.sort();
+        binds
+    }
+
+    /// Returns the actions bound to a keybind, in the order they should be applied
+    pub fn get_actions(&self, keybind: &Keybind) -> Option<&[Action]> {
+        self.binds.get(keybind).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config() {
+        let manager = KeybindManager::with_config("").unwrap();
+        assert!(manager.is_empty());
+
+        let manager = KeybindManager::with_config("// h = moveCol(-1, true)\n// Comment").unwrap();
+        assert!(manager.is_empty());
+        assert_eq!(manager.len(), 0);
+
+        let manager =
+            KeybindManager::with_config("// Comment\nh = moveCol(-1, true)\nl = moveCol(1, true)")
+                .unwrap();
+        assert!(!manager.is_empty());
+        assert_eq!(manager.len(), 2);
+    }
+
+    #[test]
+    fn test_multiple_actions() {
+        let manager = KeybindManager::with_config(
+            "x = toggleCandidate(1); moveRow(1, true)\ny = clearCell()",
+        )
+        .unwrap();
+        let x = Keybind::try_from("x".to_owned()).unwrap();
+        let kinds: Vec<_> = manager
+            .get_actions(&x)
+            .unwrap()
+            .iter()
+            .map(Action::kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                actions::ActionKind::ToggleCandidate,
+                actions::ActionKind::MoveRow
+            ]
+        );
+
+        // A single action is still a valid bind
+        let y = Keybind::try_from("y".to_owned()).unwrap();
+        assert_eq!(manager.get_actions(&y).unwrap().len(), 1);
+
+        assert!(KeybindManager::with_config("x = clearCell();").is_err());
+        assert!(KeybindManager::with_config("x = clearCell(); nope()").is_err());
+    }
+
+    #[test]
+    fn test_keybind_display() {
+        for notation in ["x", "S-1", "C-z", "A-Up", "M-Space", "Enter", "C-F5"] {
+            let keybind = Keybind::try_from(notation.to_owned()).unwrap();
+            assert_eq!(keybind.to_string(), notation);
+            assert_eq!(Keybind::try_from(keybind.to_string()).unwrap(), keybind);
+        }
+    }
+
+    #[test]
+    fn test_list_binds() {
+        let manager =
+            KeybindManager::with_config("x = clearCell()\nS-1 = toggleCandidate(1); clearCell()")
+                .unwrap();
+        assert_eq!(
+            manager.list_binds(),
+            [
+                (
+                    "S-1".to_owned(),
+                    "toggleCandidate(1); clearCell()".to_owned()
+                ),
+                ("x".to_owned(), "clearCell()".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_binds_round_trip() {
+        // Every kind of argument, and the default config
+        let config = "a = moveRow(-1, true); moveCol(2, false)
+            b = moveSkip(left); focusCorner(bottomRight); setErrorMode(ruleConflicts)
+            c = setMark(q); jumpMark(7); focusCell(8, 0); setColor(0); markForced()";
+        for manager in [
+            KeybindManager::with_config(config).unwrap(),
+            KeybindManager::with_defaults().unwrap(),
+        ] {
+            let listed: String = manager
+                .list_binds()
+                .into_iter()
+                .map(|(keybind, actions)| format!("{keybind} = {actions}\n"))
+                .collect();
+            let parsed = KeybindManager::with_config(&listed).unwrap();
+            assert_eq!(parsed.list_binds(), manager.list_binds());
+            for (keybind, actions) in &manager.binds {
+                assert_eq!(
+                    format!("{:?}", parsed.get_actions(keybind).unwrap()),
+                    format!("{actions:?}")
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_config() {
+        let manager = KeybindManager::with_defaults().unwrap();
+        let action = |notation: &str| {
+            let keybind = Keybind::try_from(notation.to_owned()).unwrap();
+            format!("{:?}", manager.get_actions(&keybind).unwrap())
+        };
+        assert_eq!(action("h"), "[MoveCol(-1, true)]");
+        assert_eq!(action("j"), "[MoveRow(1, true)]");
+        assert_eq!(action("k"), "[MoveRow(-1, true)]");
+        assert_eq!(action("l"), "[MoveCol(1, true)]");
+        assert_eq!(action("S-5"), "[ToggleCandidate(5)]");
+    }
+
+    #[test]
+    fn test_duplicate_binds() {
+        let result = KeybindManager::with_config("x = clearCell()\nx = undo()");
+        assert!(matches!(
+            result,
+            Err(KeybindManagerError::DuplicateBind(bind)) if bind == "x"
+        ));
+
+        let result = KeybindManager::with_config("A-x = clearCell()\nM-x = undo()");
+        assert!(matches!(result, Err(KeybindManagerError::DuplicateBind(_))));
+
+        // Same key with different modifiers
+        let manager = KeybindManager::with_config("x = clearCell()\nC-x = undo()").unwrap();
+        assert_eq!(manager.len(), 2);
+    }
+
+    #[test]
+    fn test_hash_comments_and_blank_lines() {
+        let config = "# Movement\nh = moveCol(-1, true)\n\n   \n  # Indented comment\n// Other style\nl = moveCol(1, true)\n";
+        let manager = KeybindManager::with_config(config).unwrap();
+        assert_eq!(manager.len(), 2);
     }
 }
@@ -1,11 +1,13 @@
 use wasm_bindgen::prelude::wasm_bindgen;
 
 mod actions;
+mod format;
 mod game_state;
 mod generation;
 mod key;
 mod keybinds;
 mod render;
+mod solver;
 
 /* #[wasm_bindgen]
 extern "C" {
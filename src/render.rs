@@ -1,99 +1,465 @@
+use serde::Deserialize;
+use thiserror::Error;
 use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
 
-use crate::game_state::{Cell, GameState};
+use crate::{
+    game_state::{Cell, CellError, GameState, CELL_COLOR_COUNT, PENCIL_COLOR_COUNT},
+    generation::GridShape,
+};
 
-const SIZE: usize = 600;
-const PADDING: usize = 3;
-const FONT_SIZE: usize = 50;
-const CANDIDATE_SIZE: usize = 15;
+/// Dimensions used to render the grid, in pixels
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct RenderConfig {
+    /// Width and height of the area the grid is drawn in
+    pub size: usize,
+    /// Space between the edges of the area and the grid
+    pub padding: usize,
+    pub font_size: usize,
+    pub candidate_size: usize,
+    /// Space between the edges of a cell and its candidates
+    pub candidate_padding: usize,
+    /// Height of the area above the grid where the HUD is drawn, when shown
+    pub hud_height: usize,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            size: 600,
+            padding: 3,
+            font_size: 50,
+            candidate_size: 15,
+            candidate_padding: 2,
+            hud_height: 30,
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Returns the config for a `size` by `size` area, every dimension keeping its proportion to
+    /// the area so the grid looks the same at any size. Dimensions never go below a pixel.
+    fn scaled_to(&self, size: usize) -> Self {
+        // Rounded to the nearest pixel
+        let scale = |value: usize| ((value * size + self.size / 2) / self.size.max(1)).max(1);
+        Self {
+            size,
+            padding: scale(self.padding),
+            font_size: scale(self.font_size),
+            candidate_size: scale(self.candidate_size),
+            candidate_padding: scale(self.candidate_padding),
+            hud_height: scale(self.hud_height),
+        }
+    }
+
+    /// Size of the cells of a `grid_size` by `grid_size` grid drawn below `hud_offset` pixels of
+    /// HUD. Never 0, even when the area is too small to fit the grid.
+    fn cell_size(&self, hud_offset: usize, grid_size: usize) -> usize {
+        let grid_px = self.size.saturating_sub(2 * self.padding + hud_offset);
+        (grid_px / grid_size).max(1)
+    }
+
+    /// True if the cells of a 9x9 grid are larger than their candidate padding, even with the
+    /// HUD shown
+    fn is_valid(&self) -> bool {
+        let grid_px = self.size.saturating_sub(2 * self.padding + self.hud_height);
+        grid_px / 9 > 2 * self.candidate_padding
+    }
+}
+
+/// Smallest canvas size accepted by `GridRenderer::resize`, cells of a smaller 9x9 grid being
+/// too small to fit their candidates
+const MIN_CANVAS_SIZE: usize = 100;
+
+// Methods exported to JS
+#[wasm_bindgen]
+impl RenderConfig {
+    /// Creates a config with the default dimensions, made for a 600x600 canvas
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// CSS colors used to draw the grid, see `GridRenderer::set_theme`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Drawn behind the whole canvas
+    pub background: String,
+    pub grid_line: String,
+    pub given_digit: String,
+    /// Digits entered by the player
+    pub user_digit: String,
+    /// Candidates in the default pencil color, the other pencil colors being fixed
+    pub candidate: String,
+    /// Border of the focused cell, unless it's a given or an error
+    pub focus_border: String,
+    /// Border of the focused cell when it's a given, which can't be edited
+    pub given_focus_border: String,
+    /// Digits drawn over the background of an error
+    pub error_digit: String,
+    /// Digits revealed by a hint
+    pub hint_digit: String,
+    /// Solution shown faintly behind empty cells in practice mode
+    pub ghost_digit: String,
+    /// Background of the cells and candidates matching the highlighted digit
+    pub highlight: String,
+    /// Background of the cells sharing a row, column or box with the focused cell
+    pub peer_highlight: String,
+    /// Marker in the corner of flagged cells, kept apart from the error backgrounds
+    pub flag_marker: String,
+    /// Cell backgrounds set by `CycleColor`, color `n` being at index `n - 1`
+    pub cell_colors: [String; CELL_COLOR_COUNT as usize],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    /// Dark digits and lines on a white background
+    pub fn light() -> Self {
+        Self {
+            background: "rgba(255,255,255,1)".to_owned(),
+            grid_line: "rgba(0,0,0,1)".to_owned(),
+            given_digit: "rgba(0,0,0,1)".to_owned(),
+            user_digit: "rgba(230,60,255,1)".to_owned(), // Purple
+            candidate: "rgba(20,20,20,1)".to_owned(),
+            focus_border: "rgba(230,60,255,1)".to_owned(), // Purple
+            given_focus_border: "rgba(128,128,128,1)".to_owned(), // Gray
+            // Black for contrast with the red background
+            error_digit: "rgba(0,0,0,1)".to_owned(),
+            hint_digit: "rgba(0,150,80,1)".to_owned(), // Green
+            ghost_digit: "rgba(20,20,20,0.12)".to_owned(),
+            highlight: "rgba(200,200,200,1)".to_owned(), // Gray
+            peer_highlight: "rgba(230,238,250,1)".to_owned(), // Faint blue
+            flag_marker: "rgba(30,110,230,1)".to_owned(), // Blue
+            cell_colors: [
+                "rgba(255,220,90,1)".to_owned(),  // Yellow
+                "rgba(140,200,255,1)".to_owned(), // Light blue
+            ],
+        }
+    }
+
+    /// Light digits and lines on a dark gray background
+    pub fn dark() -> Self {
+        Self {
+            background: "rgba(30,30,30,1)".to_owned(),
+            grid_line: "rgba(200,200,200,1)".to_owned(),
+            given_digit: "rgba(235,235,235,1)".to_owned(),
+            user_digit: "rgba(215,130,255,1)".to_owned(), // Light purple
+            candidate: "rgba(190,190,190,1)".to_owned(),
+            focus_border: "rgba(215,130,255,1)".to_owned(), // Light purple
+            given_focus_border: "rgba(150,150,150,1)".to_owned(), // Gray
+            error_digit: "rgba(255,255,255,1)".to_owned(),
+            hint_digit: "rgba(80,210,140,1)".to_owned(), // Light green
+            ghost_digit: "rgba(235,235,235,0.15)".to_owned(),
+            highlight: "rgba(80,80,80,1)".to_owned(), // Dark gray
+            peer_highlight: "rgba(45,50,62,1)".to_owned(), // Dark blue
+            flag_marker: "rgba(90,160,255,1)".to_owned(), // Light blue
+            cell_colors: [
+                "rgba(110,95,30,1)".to_owned(), // Dark yellow
+                "rgba(40,75,115,1)".to_owned(), // Dark blue
+            ],
+        }
+    }
+
+    /// Reads a theme from a JSON object, colors left out keeping their default value
+    fn from_json(json: &str) -> Result<Self, ThemeError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Invalid theme: {0}")]
+pub struct ThemeError(#[from] serde_json::Error);
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for ThemeError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// Reasons the renderer can't get hold of the canvas it draws on
+#[derive(Error, Debug)]
+pub enum RendererInitError {
+    #[error("No global window, the renderer must run in a browser")]
+    NoWindow,
+    #[error("The window has no document")]
+    NoDocument,
+    #[error("No element with the id \"canvas\" in the document")]
+    NoCanvas,
+    #[error("The element with the id \"canvas\" is not a <canvas>")]
+    NotACanvas,
+    #[error("Couldn't get a 2d context from the canvas")]
+    NoContext,
+    #[error("Invalid render config, the padding and HUD leave no room for the cells")]
+    InvalidConfig,
+}
+
+// Allow since we only ever need to send this error type to JS, never receive it from JS
+#[allow(clippy::from_over_into)]
+impl Into<JsValue> for RendererInitError {
+    fn into(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+/// CSS colors of the pencil colors candidates can be marked with, color `n` being at index
+/// `n - 2`. The first one is the default color, `Theme::candidate`.
+const PENCIL_COLORS: [&str; PENCIL_COLOR_COUNT as usize - 1] = [
+    "rgba(30,100,220,1)",
+    "rgba(30,150,60,1)",
+    "rgba(210,50,50,1)",
+    "rgba(220,130,0,1)",
+];
+
+/// Formats a duration as `m:ss`, or `h:mm:ss` past an hour
+fn format_elapsed(elapsed_ms: f64) -> String {
+    let secs = (elapsed_ms.max(0.) / 1000.) as u64;
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
+/// Obtains the top-left coordinate of a specific cell, as `(y, x)`
+fn cell_pos(row: usize, col: usize, cell_size: usize, padding: usize) -> (usize, usize) {
+    // Logic: Padding + n * cell_size, lines are drawn over the cell edges
+    (padding + row * cell_size, padding + col * cell_size)
+}
+
+/// Obtains the offset of candidate `n` (0-indexed) from the top-left corner of its cell, as
+/// `(y, x)`. Candidates are laid out in a 3x3 grid within the cell.
+fn candidate_offset(n: usize, cell_size: usize, candidate_padding: usize) -> (usize, usize) {
+    // Weird math but trust that it makes sense (fancy way of aligning along thirds with
+    // some padding on both sides of the axis)
+    let offset_size = cell_size.saturating_sub(2 * candidate_padding) / 6;
+    let x_offset = candidate_padding + (2 * (n % 3) + 1) * offset_size;
+    let y_offset = candidate_padding + 3 + (2 * (n / 3) + 1) * offset_size;
+    (y_offset, x_offset)
+}
+
+/// Obtains the cell containing a pixel, if any, as `(row, col)`
+fn cell_at_pixel(
+    x: usize,
+    y: usize,
+    cell_size: usize,
+    padding: usize,
+    grid_size: usize,
+) -> Option<(usize, usize)> {
+    let row = y.checked_sub(padding)? / cell_size.max(1);
+    let col = x.checked_sub(padding)? / cell_size.max(1);
+    (row < grid_size && col < grid_size).then_some((row, col))
+}
 
 #[wasm_bindgen]
 pub struct GridRenderer {
     ctx: CanvasRenderingContext2d,
+    /// Config as given when creating the renderer, scaled to the canvas to get `config`
+    base_config: RenderConfig,
+    config: RenderConfig,
+    theme: Theme,
     cell_size: usize,
+    /// Tints the boxes that are completed
+    shade_completed: bool,
+    /// Shades the row, column and box of the focused cell
+    highlight_peers: bool,
+    /// Draws a line of stats above the grid, which is shrunk to make room for it
+    show_hud: bool,
+    hud_color: String,
+    /// Shows the time spent on the puzzle in the HUD
+    hud_show_time: bool,
 }
 
 impl Default for GridRenderer {
+    /// Panics if the canvas can't be found, see `GridRenderer::new`
     fn default() -> Self {
-        Self::new()
+        Self::new().expect("Couldn't create the grid renderer")
     }
 }
 
 impl GridRenderer {
     fn clear_canvas(&self) {
-        self.ctx.clear_rect(0., 0., SIZE as f64, SIZE as f64);
+        let size = self.config.size as f64;
+        self.ctx.clear_rect(0., 0., size, size);
+        // Cleared pixels are transparent, paint the theme's background over them
+        self.ctx
+            .set_fill_style(&self.theme.background.as_str().into());
+        self.ctx.fill_rect(0., 0., size, size);
     }
 
     /// Draws the grid lines
-    fn draw_grid(&self) {
-        // Size might not be a multiple of 9, make sure lines don't overflow
-        let line_end = (self.cell_size * 9 + PADDING + 1) as f64;
+    fn draw_grid(&self, shape: GridShape) {
+        // Size might not be a multiple of the grid size, make sure lines don't overflow
+        let padding = self.config.padding;
+        let top = padding + self.hud_offset();
+        let line_start = padding.saturating_sub(1) as f64;
+        let line_end = (self.cell_size * shape.size() + padding + 1) as f64;
+        // Vertical lines start below the HUD
+        let line_top = top.saturating_sub(1) as f64;
+        let line_bottom = (self.cell_size * shape.size() + top + 1) as f64;
 
-        self.ctx.set_stroke_style(&"rgba(0,0,0,1.0)".into());
+        self.ctx
+            .set_stroke_style(&self.theme.grid_line.as_str().into());
 
         // Draw horizontal lines
-        for (i, y) in (PADDING..SIZE).step_by(self.cell_size).enumerate() {
+        for i in 0..=shape.size() {
+            let y = top + i * self.cell_size;
             self.ctx.begin_path();
             // Draw heavier lines for box outlines
-            if i % 3 == 0 {
+            if i % shape.box_rows == 0 {
                 self.ctx.set_line_width(3.0);
             } else {
                 self.ctx.set_line_width(1.0);
             }
             let y = y as f64;
-            self.ctx.move_to((PADDING - 1) as f64, y);
+            self.ctx.move_to(line_start, y);
             self.ctx.line_to(line_end, y);
             self.ctx.stroke();
         }
 
         // Vertical lines
-        for (i, x) in (PADDING..SIZE).step_by(self.cell_size).enumerate() {
+        for i in 0..=shape.size() {
+            let x = padding + i * self.cell_size;
             self.ctx.begin_path();
-            if i % 3 == 0 {
+            if i % shape.box_cols == 0 {
                 self.ctx.set_line_width(3.0);
             } else {
                 self.ctx.set_line_width(1.0);
             }
 
             let x = x as f64;
-            self.ctx.move_to(x, (PADDING - 1) as f64);
-            self.ctx.line_to(x, line_end);
+            self.ctx.move_to(x, line_top);
+            self.ctx.line_to(x, line_bottom);
             self.ctx.stroke();
         }
     }
 
+    /// Tints the background of every completed box
+    fn draw_completed_boxes(&self, game_state: &GameState) {
+        let shape = game_state.shape();
+        let boxes_per_row = shape.size() / shape.box_cols;
+
+        self.ctx.set_fill_style(&"rgba(0,160,0,0.12)".into()); // Faint green
+        for box_idx in game_state.completed_boxes() {
+            let row = box_idx / boxes_per_row * shape.box_rows;
+            let col = box_idx % boxes_per_row * shape.box_cols;
+            let (top_y, top_x) = self.get_cell_pos(row, col);
+            self.ctx.fill_rect(
+                top_x as f64,
+                top_y as f64,
+                (self.cell_size * shape.box_cols) as f64,
+                (self.cell_size * shape.box_rows) as f64,
+            );
+        }
+    }
+
     fn draw_cells(&self, game_state: &GameState) {
         let grid = game_state.grid();
+        let peers = if self.highlight_peers {
+            let (row, col) = game_state.focused_cell_coord();
+            game_state.peers(row as usize, col as usize)
+        } else {
+            Vec::new()
+        };
 
         for (row, row_vec) in grid.iter().enumerate() {
             for (col, cell) in row_vec.iter().enumerate() {
-                if game_state.highlighted_digit().is_some()
-                    && game_state.highlighted_digit() == cell.digit
-                {
-                    // Highlighted digit is not None and current cell has same digit, change bg
-                    // color
-                    self.draw_cell_background(row, col, "rgba(200,200,200,1)"); // Gray
+                // Drawn first so that everything else goes over it
+                if peers.contains(&(row, col)) {
+                    self.draw_cell_background(row, col, &self.theme.peer_highlight);
+                }
+                if cell.color != 0 {
+                    self.draw_cell_background(
+                        row,
+                        col,
+                        &self.theme.cell_colors[cell.color as usize - 1],
+                    );
                 }
 
-                if let Some(digit) = cell.digit {
-                    let expected_value = game_state.expected_value(row, col);
-                    // Only mark as error if show_errors is set AND user value is wrong
-                    let is_error = game_state.show_errors() && digit != expected_value;
-                    if is_error {
-                        // Set bg color to red to indicate error
-                        self.draw_cell_background(row, col, "rgba(220,0,0,1)");
+                if game_state.is_digit_highlighted(row, col) {
+                    self.draw_cell_background(row, col, &self.theme.highlight);
+                }
+
+                if cell.digit.is_some() {
+                    // Only mark errors shown by the error mode
+                    let error = game_state.cell_error(row, col);
+                    match error {
+                        // Set bg color to red to indicate a wrong digit
+                        Some(CellError::Mismatch) => {
+                            self.draw_cell_background(row, col, "rgba(220,0,0,1)")
+                        }
+                        // Orange for digits breaking the rules
+                        Some(CellError::Conflict) => {
+                            self.draw_cell_background(row, col, "rgba(255,140,0,1)")
+                        }
+                        None => {}
                     }
+                    let is_error = error.is_some();
 
                     self.write_cell_digit(row, col, cell, is_error);
                 } else {
+                    if let Some(digit) = game_state.ghost_digit(row, col) {
+                        self.write_digit(row, col, digit, &self.theme.ghost_digit);
+                    }
+                    if game_state.has_highlighted_candidate(row, col) {
+                        let digit = game_state.highlighted_digit().unwrap();
+                        self.draw_candidate_highlight(row, col, digit);
+                    }
                     // Render candidates
                     self.write_cell_candidates(row, col, &cell.candidates);
                 }
+
+                if cell.flagged {
+                    self.draw_flag_marker(row, col);
+                }
             }
         }
     }
 
+    /// Tints the area behind a candidate, drawn under the candidate itself
+    fn draw_candidate_highlight(&self, row: usize, col: usize, digit: u8) {
+        let (row_pos, col_pos) = self.get_cell_pos(row, col);
+        let (y_offset, x_offset) = candidate_offset(
+            digit as usize - 1,
+            self.cell_size,
+            self.config.candidate_padding,
+        );
+        // Centered on the candidate, a third of the cell wide
+        let size = (self.cell_size / 3) as f64;
+        let x = (col_pos + x_offset) as f64 - size / 2.;
+        let y = (row_pos + y_offset) as f64 - size / 2.;
+
+        self.ctx
+            .set_fill_style(&self.theme.highlight.as_str().into());
+        self.ctx.fill_rect(x, y, size, size);
+    }
+
+    /// Draws a small triangle in the top-right corner of a flagged cell
+    fn draw_flag_marker(&self, row: usize, col: usize) {
+        let (top_y, top_x) = self.get_cell_pos(row, col);
+        let right_x = (top_x + self.cell_size) as f64;
+        let top_y = top_y as f64;
+        let marker_size = (self.cell_size / 4) as f64;
+
+        self.ctx
+            .set_fill_style(&self.theme.flag_marker.as_str().into());
+        self.ctx.begin_path();
+        self.ctx.move_to(right_x - marker_size, top_y);
+        self.ctx.line_to(right_x, top_y);
+        self.ctx.line_to(right_x, top_y + marker_size);
+        self.ctx.close_path();
+        self.ctx.fill();
+    }
+
     fn draw_cell_background(&self, row: usize, col: usize, color: &str) {
         let (top_y, top_x) = self.get_cell_pos(row, col);
         self.ctx.set_fill_style(&color.into());
@@ -106,11 +472,21 @@ impl GridRenderer {
     }
 
     fn draw_highlighted_cell_border(&self, game_state: &GameState) {
-        let focused_cell = game_state.focused_cell_coord();
-        let (top_y, top_x) = self.get_cell_pos(focused_cell.0.into(), focused_cell.1.into());
+        let (row, col) = game_state.focused_cell_coord();
+        let (row, col) = (row as usize, col as usize);
+        let (top_y, top_x) = self.get_cell_pos(row, col);
+
+        // Border color tells whether the cell can be edited, and if its digit is a mistake
+        let color = if game_state.cell_error(row, col).is_some() {
+            "rgba(220,0,0,1)" // Red
+        } else if game_state.grid()[row][col].is_given {
+            &self.theme.given_focus_border
+        } else {
+            &self.theme.focus_border
+        };
 
         // Draw highlighted border
-        self.ctx.set_stroke_style(&"rgba(230,60,255,1)".into()); // Purple
+        self.ctx.set_stroke_style(&color.into());
         self.ctx.set_line_width(3.);
         self.ctx.stroke_rect(
             top_x as f64,
@@ -125,23 +501,31 @@ impl GridRenderer {
         let digit = cell.digit.unwrap();
         assert!((1..=9).contains(&digit));
 
+        // Set digit color (differenciate givens and user inputs)
+        // Errors have their own color for contrast w/ red background
+        if is_error {
+            self.write_digit(row, col, digit, &self.theme.error_digit);
+        } else if cell.is_given {
+            self.write_digit(row, col, digit, &self.theme.given_digit);
+        } else if cell.is_hint {
+            self.write_digit(row, col, digit, &self.theme.hint_digit);
+        } else {
+            self.write_digit(row, col, digit, &self.theme.user_digit);
+        }
+    }
+
+    /// Writes a digit centered in a cell
+    fn write_digit(&self, row: usize, col: usize, digit: u8, color: &str) {
         let (row_pos, col_pos) = self.get_cell_pos(row, col);
         // Returned values point to top-left corner of cell, but we want
         // y to be bottom of text and x to be center of text.
         let x_pos = col_pos + self.cell_size / 2;
-        let y_pos = row_pos + 4 + self.cell_size / 2; // Y pos needs a small offset for some reason
+        // Y pos needs a small offset for some reason, 4px for the default font size
+        let y_pos = row_pos + self.config.font_size / 12 + self.cell_size / 2;
 
-        // Set digit color (differenciate givens and user inputs)
-        // Digit should be black if given cell or invalid value (for contrast w/ red background)
-        if cell.is_given || is_error {
-            // Black
-            self.ctx.set_fill_style(&"rgba(0,0,0,1)".into());
-        } else {
-            // Purple
-            self.ctx.set_fill_style(&"rgba(230,60,255,1)".into());
-        }
-
-        self.ctx.set_font(&format!("{}px consolas", FONT_SIZE));
+        self.ctx.set_fill_style(&color.into());
+        self.ctx
+            .set_font(&format!("{}px consolas", self.config.font_size));
         self.ctx.set_text_align("center");
         self.ctx.set_text_baseline("middle");
         self.ctx
@@ -149,25 +533,27 @@ impl GridRenderer {
             .unwrap();
     }
 
-    fn write_cell_candidates(&self, row: usize, col: usize, candidates: &[bool; 9]) {
+    /// Writes the candidates of a cell in their pencil color
+    fn write_cell_candidates(&self, row: usize, col: usize, candidates: &[u8; 9]) {
         let (row_pos, col_pos) = self.get_cell_pos(row, col);
-        for (n, has_candidate) in candidates.iter().enumerate() {
-            if !has_candidate {
+        for (n, &color) in candidates.iter().enumerate() {
+            if color == 0 {
                 // Cell doesn't have this candidate, skip
                 continue;
             }
             // Calculate candidate digit offset in cell
-            // Weird math but trust that it makes sense (fancy way of aligning along thirds with
-            // some padding on both sides of the axis)
-            const CANDIDATE_PADDING: usize = 2;
-            let offset_size = (self.cell_size - 2 * CANDIDATE_PADDING) / 6;
-            let x_offset = CANDIDATE_PADDING + (2 * (n % 3) + 1) * offset_size;
-            let y_offset = CANDIDATE_PADDING + 3 + (2 * (n / 3) + 1) * offset_size;
+            let (y_offset, x_offset) =
+                candidate_offset(n, self.cell_size, self.config.candidate_padding);
             let x_pos = (col_pos + x_offset) as f64;
             let y_pos = (row_pos + y_offset) as f64;
 
-            self.ctx.set_fill_style(&"rgba(20,20,20,1)".into()); // Dark gray
-            self.ctx.set_font(&format!("{}px consolas", CANDIDATE_SIZE));
+            let color = match color {
+                1 => &self.theme.candidate,
+                n => PENCIL_COLORS[n as usize - 2],
+            };
+            self.ctx.set_fill_style(&color.into());
+            self.ctx
+                .set_font(&format!("{}px consolas", self.config.candidate_size));
             self.ctx.set_text_align("center");
             self.ctx.set_text_baseline("middle");
             self.ctx
@@ -176,43 +562,326 @@ impl GridRenderer {
         }
     }
 
+    /// Draws the moves, mistakes and elapsed time in the area reserved above the grid
+    fn draw_hud(&self, game_state: &GameState) {
+        let mut text = format!(
+            "Moves: {}   Mistakes: {}",
+            game_state.moves(),
+            game_state.mistakes()
+        );
+        if self.hud_show_time {
+            text += &format!("   Time: {}", format_elapsed(game_state.elapsed_ms()));
+        }
+
+        self.ctx.set_fill_style(&self.hud_color.as_str().into());
+        self.ctx
+            .set_font(&format!("{}px consolas", self.config.hud_height * 2 / 3));
+        self.ctx.set_text_align("left");
+        self.ctx.set_text_baseline("middle");
+        self.ctx
+            .fill_text(
+                &text,
+                self.config.padding as f64,
+                (self.config.padding + self.config.hud_height / 2) as f64,
+            )
+            .unwrap();
+    }
+
+    /// Vertical space taken by the HUD above the grid
+    fn hud_offset(&self) -> usize {
+        if self.show_hud {
+            self.config.hud_height
+        } else {
+            0
+        }
+    }
+
     /// Obtains the top-left coordinate of a specific cell
     fn get_cell_pos(&self, row: usize, col: usize) -> (usize, usize) {
-        // Logic: Padding + n * 1px for lines separating cells + n * cell_size
-        let row_pos = PADDING + (row * self.cell_size);
-        let col_pos = PADDING + (col * self.cell_size);
-        (row_pos, col_pos)
+        let (y, x) = cell_pos(row, col, self.cell_size, self.config.padding);
+        (y + self.hud_offset(), x)
     }
 }
 
 // Methods exported to JS
 #[wasm_bindgen]
 impl GridRenderer {
-    pub fn new() -> Self {
+    /// Creates a renderer drawing on the `#canvas` element of the page. Fails with a message
+    /// explaining what is missing if there is no such canvas.
+    pub fn new() -> Result<GridRenderer, RendererInitError> {
+        Self::with_config(RenderConfig::default())
+    }
+
+    /// Creates a renderer drawing the grid with the specified dimensions, scaled to the actual
+    /// size of the canvas (the smallest of its width and height). Fails like `new`, or if the
+    /// paddings and HUD height are too large for the cells of a 9x9 grid to fit.
+    pub fn with_config(base_config: RenderConfig) -> Result<GridRenderer, RendererInitError> {
+        if !base_config.is_valid() {
+            return Err(RendererInitError::InvalidConfig);
+        }
+
         // Obtain 2d context for canvas
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
-        let canvas = document.get_element_by_id("canvas").unwrap();
-        let canvas = canvas.dyn_into::<web_sys::HtmlCanvasElement>().unwrap();
+        let window = web_sys::window().ok_or(RendererInitError::NoWindow)?;
+        let document = window.document().ok_or(RendererInitError::NoDocument)?;
+        let canvas = document
+            .get_element_by_id("canvas")
+            .ok_or(RendererInitError::NoCanvas)?;
+        let canvas = canvas
+            .dyn_into::<web_sys::HtmlCanvasElement>()
+            .map_err(|_| RendererInitError::NotACanvas)?;
 
         let ctx = canvas
             .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::CanvasRenderingContext2d>()
-            .unwrap();
+            .ok()
+            .flatten()
+            .and_then(|ctx| ctx.dyn_into::<web_sys::CanvasRenderingContext2d>().ok())
+            .ok_or(RendererInitError::NoContext)?;
 
-        // Calculate cell_size
-        let cell_size = (SIZE - 2 * PADDING) / 9;
+        let canvas_size = canvas.width().min(canvas.height()) as usize;
+        let config = base_config.scaled_to(canvas_size);
+        let cell_size = config.cell_size(0, 9);
 
-        Self { ctx, cell_size }
+        Ok(Self {
+            ctx,
+            base_config,
+            config,
+            theme: Theme::default(),
+            cell_size,
+            shade_completed: false,
+            highlight_peers: true,
+            show_hud: false,
+            hud_color: "rgba(20,20,20,1)".to_owned(),
+            hud_show_time: true,
+        })
+    }
+
+    /// Resizes the canvas to `size` by `size` pixels, scaling the dimensions of the grid with it.
+    /// Meant to be called when the window is resized. Sizes below 100 pixels are raised to 100.
+    pub fn resize(&mut self, size: usize) {
+        let size = size.max(MIN_CANVAS_SIZE);
+        if let Some(canvas) = self.ctx.canvas() {
+            canvas.set_width(size as u32);
+            canvas.set_height(size as u32);
+        }
+        self.config = self.base_config.scaled_to(size);
+    }
+
+    /// Overrides colors of the grid with a JSON object whose keys are the fields of `Theme`, e.g.
+    /// `{ "given_digit": "white" }`. Colors left out are reset to their default value.
+    pub fn set_theme(&mut self, theme_json: &str) -> Result<(), ThemeError> {
+        self.theme = Theme::from_json(theme_json)?;
+        Ok(())
+    }
+
+    /// Switches between the `Theme::dark` and `Theme::light` presets, replacing any theme set by
+    /// `set_theme`. The HUD text color follows.
+    pub fn set_dark_mode(&mut self, enabled: bool) {
+        let (theme, hud_color) = if enabled {
+            (Theme::dark(), "rgba(230,230,230,1)")
+        } else {
+            (Theme::light(), "rgba(20,20,20,1)")
+        };
+        self.theme = theme;
+        self.hud_color = hud_color.to_owned();
+    }
+
+    pub fn set_shade_completed(&mut self, value: bool) {
+        self.shade_completed = value;
+    }
+
+    /// Shades the row, column and box of the focused cell, on by default
+    pub fn set_highlight_peers(&mut self, value: bool) {
+        self.highlight_peers = value;
+    }
+
+    /// Shows moves, mistakes and time above the grid, which shrinks by `RenderConfig::hud_height`
+    pub fn set_show_hud(&mut self, value: bool) {
+        self.show_hud = value;
+    }
+
+    /// Sets the CSS color of the HUD text
+    pub fn set_hud_color(&mut self, color: String) {
+        self.hud_color = color;
+    }
+
+    /// Shows the time of `GameState::elapsed_ms` in the HUD, on by default. The game state
+    /// timer must be ticked for it to move, see `GameState::tick_timer`.
+    pub fn set_hud_show_time(&mut self, value: bool) {
+        self.hud_show_time = value;
+    }
+
+    /// Returns the `[row, col]` of the cell under a canvas pixel, as of the last render, or
+    /// nothing if the pixel is outside of the grid
+    pub fn cell_at_pixel(&self, x: f64, y: f64, game_state: &GameState) -> Option<Vec<u8>> {
+        if x < 0. || y < 0. {
+            return None;
+        }
+        let (row, col) = cell_at_pixel(
+            x as usize,
+            (y as usize).checked_sub(self.hud_offset())?,
+            self.cell_size,
+            self.config.padding,
+            game_state.size(),
+        )?;
+        Some(vec![row as u8, col as u8])
     }
 
     /// Renders the grid to the canvas
-    pub fn render(&self, game_state: &GameState) {
+    pub fn render(&mut self, game_state: &GameState) {
+        // Cells take up the whole canvas no matter the size of the grid, apart from the HUD
+        self.cell_size = self.config.cell_size(self.hud_offset(), game_state.size());
+
         self.clear_canvas();
+        if self.show_hud {
+            self.draw_hud(game_state);
+        }
+        if self.shade_completed {
+            self.draw_completed_boxes(game_state);
+        }
         self.draw_cells(game_state);
-        self.draw_grid();
+        self.draw_grid(game_state.shape());
         self.draw_highlighted_cell_border(game_state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_pos() {
+        // Default config on a 9x9 grid: (600 - 2 * 3) / 9 = 66px cells
+        assert_eq!(cell_pos(0, 0, 66, 3), (3, 3));
+        assert_eq!(cell_pos(0, 8, 66, 3), (3, 531));
+        assert_eq!(cell_pos(4, 2, 66, 3), (267, 135));
+        // 6x6 grid: 99px cells
+        assert_eq!(cell_pos(5, 5, 99, 3), (498, 498));
+    }
+
+    #[test]
+    fn test_candidate_offset() {
+        // Candidates are centered on thirds of the cell
+        assert_eq!(candidate_offset(0, 66, 2), (15, 12));
+        assert_eq!(candidate_offset(4, 66, 2), (35, 32));
+        assert_eq!(candidate_offset(8, 66, 2), (55, 52));
+        // Offsets stay within the cell
+        let (y, x) = candidate_offset(8, 99, 2);
+        assert!(y < 99 && x < 99);
+    }
+
+    #[test]
+    fn test_cell_at_pixel() {
+        for (cell_size, grid_size) in [(66, 9), (99, 6)] {
+            for row in 0..grid_size {
+                for col in 0..grid_size {
+                    let (y, x) = cell_pos(row, col, cell_size, 3);
+                    let expected = Some((row, col));
+                    assert_eq!(cell_at_pixel(x, y, cell_size, 3, grid_size), expected);
+                    // Last pixel of the cell
+                    let (x, y) = (x + cell_size - 1, y + cell_size - 1);
+                    assert_eq!(cell_at_pixel(x, y, cell_size, 3, grid_size), expected);
+                }
+            }
+        }
+
+        // Padding and pixels past the grid
+        assert_eq!(cell_at_pixel(2, 10, 66, 3, 9), None);
+        assert_eq!(cell_at_pixel(10, 2, 66, 3, 9), None);
+        assert_eq!(cell_at_pixel(597, 10, 66, 3, 9), None);
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(0.), "0:00");
+        assert_eq!(format_elapsed(65_999.), "1:05");
+        assert_eq!(format_elapsed(3_725_000.), "1:02:05");
+    }
+
+    #[test]
+    fn test_scaled_config() {
+        let config = RenderConfig::default();
+        let same = config.scaled_to(600);
+        assert_eq!(same.font_size, config.font_size);
+        assert_eq!(same.candidate_size, config.candidate_size);
+
+        let half = config.scaled_to(300);
+        assert_eq!(half.size, 300);
+        assert_eq!(half.font_size, 25);
+        // 7.5 rounds up
+        assert_eq!(half.candidate_size, 8);
+        assert_eq!(half.hud_height, 15);
+
+        let double = config.scaled_to(1200);
+        assert_eq!(double.font_size, 100);
+        assert_eq!(double.padding, 6);
+
+        // Tiny canvases still get visible dimensions
+        assert_eq!(config.scaled_to(10).candidate_padding, 1);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(RenderConfig::default().is_valid());
+        for invalid in [
+            RenderConfig {
+                padding: 300,
+                ..Default::default()
+            },
+            RenderConfig {
+                hud_height: 600,
+                ..Default::default()
+            },
+            // Cells of 63px with the HUD
+            RenderConfig {
+                candidate_padding: 32,
+                ..Default::default()
+            },
+            RenderConfig {
+                size: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(!invalid.is_valid());
+        }
+    }
+
+    #[test]
+    fn test_tiny_size() {
+        // What `resize` and `render` compute for a canvas of 10 pixels, with the HUD shown
+        let tiny = RenderConfig::default().scaled_to(10);
+        let cell_size = tiny.cell_size(tiny.hud_height, 9);
+        assert_eq!(cell_size, 1);
+        // Cells smaller than their candidate padding
+        assert_eq!(
+            candidate_offset(8, cell_size, tiny.candidate_padding),
+            (4, 1)
+        );
+        assert_eq!(cell_at_pixel(5, 5, 0, 1, 9), Some((4, 4)));
+        // Nothing fits in an area smaller than its padding
+        assert_eq!(RenderConfig::default().scaled_to(0).cell_size(0, 9), 1);
+
+        // `resize` never goes below the minimum size, where candidates fit in their cells
+        assert!(RenderConfig::default()
+            .scaled_to(MIN_CANVAS_SIZE)
+            .is_valid());
+    }
+
+    #[test]
+    fn test_theme_from_json() {
+        let theme = Theme::from_json(r#"{ "given_digit": "white" }"#).unwrap();
+        assert_eq!(theme.given_digit, "white");
+        // Other colors keep their default
+        assert_eq!(theme.user_digit, Theme::default().user_digit);
+
+        assert_eq!(Theme::from_json("{}").unwrap(), Theme::default());
+        let theme = Theme::from_json(r#"{ "cell_colors": ["pink", "teal"] }"#).unwrap();
+        assert_eq!(theme.cell_colors, ["pink", "teal"]);
+        assert!(Theme::from_json(r#"{ "given_digit": 3 }"#).is_err());
+    }
+
+    #[test]
+    fn test_theme_presets() {
+        assert_ne!(Theme::dark().background, Theme::light().background);
+        assert_eq!(Theme::default(), Theme::light());
+    }
+}
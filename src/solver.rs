@@ -0,0 +1,489 @@
+use std::fmt::{self, Display};
+
+use wasm_bindgen::prelude::*;
+
+use crate::generation::GridShape;
+
+/// Candidates of every cell of a grid, index `n` of a cell being true if `n + 1` is a candidate
+pub type Candidates = Vec<Vec<[bool; 9]>>;
+
+/// A single deduction made by the logical solver
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveStep {
+    /// `digit` is the only candidate left in the cell
+    NakedSingle { row: usize, col: usize, digit: u8 },
+    /// The cell is the only one of a row, column or box where `digit` can go
+    HiddenSingle { row: usize, col: usize, digit: u8 },
+    /// The candidates for `digit` in a box all lie on the same row or column, so `digit` can be
+    /// removed from the cells of that line outside of the box
+    PointingPair {
+        digit: u8,
+        eliminations: Vec<(usize, usize)>,
+    },
+    /// The candidates for `digit` in a row or column all lie in the same box, so `digit` can be
+    /// removed from the other cells of that box
+    BoxLineReduction {
+        digit: u8,
+        eliminations: Vec<(usize, usize)>,
+    },
+}
+
+impl Display for SolveStep {
+    /// Describes the deduction for the player, with 1-based rows and columns
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn cell_list(cells: &[(usize, usize)]) -> String {
+            let cells: Vec<String> = cells
+                .iter()
+                .map(|(r, c)| format!("row {}, column {}", r + 1, c + 1))
+                .collect();
+            cells.join("; ")
+        }
+
+        match self {
+            SolveStep::NakedSingle { row, col, digit } => write!(
+                f,
+                "Naked single: {digit} is the only candidate left at row {}, column {}",
+                row + 1,
+                col + 1
+            ),
+            SolveStep::HiddenSingle { row, col, digit } => write!(
+                f,
+                "Hidden single: row {}, column {} is the only place left for {digit} in its row, \
+                 column or box",
+                row + 1,
+                col + 1
+            ),
+            SolveStep::PointingPair {
+                digit,
+                eliminations,
+            } => write!(
+                f,
+                "Pointing pair: {digit} is confined to one line of a box, remove it from {}",
+                cell_list(eliminations)
+            ),
+            SolveStep::BoxLineReduction {
+                digit,
+                eliminations,
+            } => write!(
+                f,
+                "Box/line reduction: {digit} is confined to one box of a line, remove it from {}",
+                cell_list(eliminations)
+            ),
+        }
+    }
+}
+
+/// Techniques needed to solve a puzzle, as found by `rate_difficulty`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DifficultyReport {
+    pub naked_singles: usize,
+    pub hidden_singles: usize,
+    pub pointing_pairs: usize,
+    pub box_line_reductions: usize,
+    /// Total number of deductions made
+    pub steps: usize,
+    /// False if the solver got stuck, meaning the puzzle needs techniques it doesn't know
+    pub solved: bool,
+}
+
+/// Returns the candidates of every empty cell of a grid (0 meaning an empty cell), i.e. the
+/// digits not already present in one of its peers
+pub fn initial_candidates(grid: &[Vec<u8>], shape: GridShape) -> Candidates {
+    let size = shape.size();
+    (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    let mut candidates = [false; 9];
+                    if grid[row][col] == 0 {
+                        candidates[..size].fill(true);
+                        for (r, c) in shape.peers(row, col) {
+                            if grid[r][c] != 0 {
+                                candidates[grid[r][c] as usize - 1] = false;
+                            }
+                        }
+                    }
+                    candidates
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds the next deduction, trying the simplest techniques first. Returns `None` if no known
+/// technique applies.
+pub fn step(grid: &[Vec<u8>], candidates: &Candidates, shape: GridShape) -> Option<SolveStep> {
+    naked_single(grid, candidates)
+        .or_else(|| hidden_single(grid, candidates, shape))
+        .or_else(|| pointing_pair(grid, candidates, shape))
+        .or_else(|| box_line_reduction(grid, candidates, shape))
+}
+
+fn naked_single(grid: &[Vec<u8>], candidates: &Candidates) -> Option<SolveStep> {
+    for (row, row_vec) in grid.iter().enumerate() {
+        for (col, &digit) in row_vec.iter().enumerate() {
+            if digit != 0 {
+                continue;
+            }
+            let mut cell_candidates = (0..9).filter(|&n| candidates[row][col][n]);
+            if let (Some(n), None) = (cell_candidates.next(), cell_candidates.next()) {
+                return Some(SolveStep::NakedSingle {
+                    row,
+                    col,
+                    digit: n as u8 + 1,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn hidden_single(grid: &[Vec<u8>], candidates: &Candidates, shape: GridShape) -> Option<SolveStep> {
+    for unit in shape.units() {
+        for digit in 1..=shape.size() as u8 {
+            let n = digit as usize - 1;
+            let mut cells = unit
+                .iter()
+                .filter(|&&(r, c)| grid[r][c] == 0 && candidates[r][c][n]);
+            if let (Some(&(row, col)), None) = (cells.next(), cells.next()) {
+                return Some(SolveStep::HiddenSingle { row, col, digit });
+            }
+        }
+    }
+    None
+}
+
+fn pointing_pair(grid: &[Vec<u8>], candidates: &Candidates, shape: GridShape) -> Option<SolveStep> {
+    let size = shape.size();
+    for box_idx in 0..size {
+        let box_cells = shape.box_cells(box_idx);
+        for digit in 1..=size as u8 {
+            let n = digit as usize - 1;
+            let cells: Vec<(usize, usize)> = box_cells
+                .iter()
+                .copied()
+                .filter(|&(r, c)| grid[r][c] == 0 && candidates[r][c][n])
+                .collect();
+            if cells.len() < 2 {
+                continue;
+            }
+
+            let (first_row, first_col) = cells[0];
+            let line: Vec<(usize, usize)> = if cells.iter().all(|&(r, _)| r == first_row) {
+                (0..size).map(|c| (first_row, c)).collect()
+            } else if cells.iter().all(|&(_, c)| c == first_col) {
+                (0..size).map(|r| (r, first_col)).collect()
+            } else {
+                continue;
+            };
+
+            let eliminations: Vec<(usize, usize)> = line
+                .into_iter()
+                .filter(|cell| !box_cells.contains(cell))
+                .filter(|&(r, c)| grid[r][c] == 0 && candidates[r][c][n])
+                .collect();
+            if !eliminations.is_empty() {
+                return Some(SolveStep::PointingPair {
+                    digit,
+                    eliminations,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn box_line_reduction(
+    grid: &[Vec<u8>],
+    candidates: &Candidates,
+    shape: GridShape,
+) -> Option<SolveStep> {
+    let size = shape.size();
+    // Rows then columns, see `GridShape::units`
+    for line in shape.units().into_iter().take(2 * size) {
+        for digit in 1..=size as u8 {
+            let n = digit as usize - 1;
+            let cells: Vec<(usize, usize)> = line
+                .iter()
+                .copied()
+                .filter(|&(r, c)| grid[r][c] == 0 && candidates[r][c][n])
+                .collect();
+            let Some(&(first_row, first_col)) = cells.first() else {
+                continue;
+            };
+            let box_idx = shape.box_index(first_row, first_col);
+            if cells.len() < 2 || !cells.iter().all(|&(r, c)| shape.box_index(r, c) == box_idx) {
+                continue;
+            }
+
+            let eliminations: Vec<(usize, usize)> = shape
+                .box_cells(box_idx)
+                .into_iter()
+                .filter(|cell| !line.contains(cell))
+                .filter(|&(r, c)| grid[r][c] == 0 && candidates[r][c][n])
+                .collect();
+            if !eliminations.is_empty() {
+                return Some(SolveStep::BoxLineReduction {
+                    digit,
+                    eliminations,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Applies a deduction to the grid and its candidates
+pub fn apply_step(
+    grid: &mut [Vec<u8>],
+    candidates: &mut Candidates,
+    shape: GridShape,
+    step: &SolveStep,
+) {
+    match *step {
+        SolveStep::NakedSingle { row, col, digit }
+        | SolveStep::HiddenSingle { row, col, digit } => {
+            grid[row][col] = digit;
+            candidates[row][col] = [false; 9];
+            for (r, c) in shape.peers(row, col) {
+                candidates[r][c][digit as usize - 1] = false;
+            }
+        }
+        SolveStep::PointingPair {
+            digit,
+            ref eliminations,
+        }
+        | SolveStep::BoxLineReduction {
+            digit,
+            ref eliminations,
+        } => {
+            for &(r, c) in eliminations {
+                candidates[r][c][digit as usize - 1] = false;
+            }
+        }
+    }
+}
+
+/// Returns true if a grid (0 meaning an empty cell) can be filled using naked singles alone,
+/// the easiest technique there is
+pub fn solves_with_naked_singles(grid: &[Vec<u8>], shape: GridShape) -> bool {
+    let mut grid = grid.to_vec();
+    let mut candidates = initial_candidates(&grid, shape);
+    while let Some(step) = naked_single(&grid, &candidates) {
+        apply_step(&mut grid, &mut candidates, shape, &step);
+    }
+    grid.iter().flatten().all(|&digit| digit != 0)
+}
+
+/// Fills a grid (0 meaning an empty cell) using only naked and hidden singles, so without ever
+/// guessing. Returns `None` if singles aren't enough to fill every cell, or if the filled grid
+/// breaks the rules, e.g. because of a wrong digit in the starting grid.
+pub fn solve_singles(grid: &[Vec<u8>], shape: GridShape) -> Option<Vec<Vec<u8>>> {
+    let mut grid = grid.to_vec();
+    let mut candidates = initial_candidates(&grid, shape);
+    while let Some(step) =
+        naked_single(&grid, &candidates).or_else(|| hidden_single(&grid, &candidates, shape))
+    {
+        apply_step(&mut grid, &mut candidates, shape, &step);
+    }
+
+    let valid = shape.units().iter().all(|unit| {
+        let mut seen = [false; 9];
+        unit.iter().all(|&(r, c)| {
+            let digit = grid[r][c];
+            digit != 0 && !std::mem::replace(&mut seen[digit as usize - 1], true)
+        })
+    });
+    valid.then_some(grid)
+}
+
+/// Solves a puzzle (0 meaning an empty cell) using only logical techniques, counting how many
+/// times each one was needed
+pub fn rate_difficulty(grid: &[Vec<u8>], shape: GridShape) -> DifficultyReport {
+    let mut grid = grid.to_vec();
+    let mut candidates = initial_candidates(&grid, shape);
+    let mut report = DifficultyReport::default();
+
+    while let Some(step) = step(&grid, &candidates, shape) {
+        match step {
+            SolveStep::NakedSingle { .. } => report.naked_singles += 1,
+            SolveStep::HiddenSingle { .. } => report.hidden_singles += 1,
+            SolveStep::PointingPair { .. } => report.pointing_pairs += 1,
+            SolveStep::BoxLineReduction { .. } => report.box_line_reductions += 1,
+        }
+        report.steps += 1;
+        apply_step(&mut grid, &mut candidates, shape, &step);
+    }
+
+    report.solved = grid.iter().flatten().all(|&digit| digit != 0);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_grid(grid: [[u8; 9]; 9]) -> Vec<Vec<u8>> {
+        grid.iter().map(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_rate_difficulty() {
+        // Puzzle solvable with singles only
+        let grid = to_grid([
+            [0, 0, 3, 0, 2, 0, 6, 0, 0],
+            [9, 0, 0, 3, 0, 5, 0, 0, 1],
+            [0, 0, 1, 8, 0, 6, 4, 0, 0],
+            [0, 0, 8, 1, 0, 2, 9, 0, 0],
+            [7, 0, 0, 0, 0, 0, 0, 0, 8],
+            [0, 0, 6, 7, 0, 8, 2, 0, 0],
+            [0, 0, 2, 6, 0, 9, 5, 0, 0],
+            [8, 0, 0, 2, 0, 3, 0, 0, 9],
+            [0, 0, 5, 0, 1, 0, 3, 0, 0],
+        ]);
+        let report = rate_difficulty(&grid, GridShape::CLASSIC);
+        assert!(report.solved);
+        let empty_cells = grid.iter().flatten().filter(|&&d| d == 0).count();
+        assert_eq!(report.naked_singles + report.hidden_singles, empty_cells);
+        assert_eq!(
+            report.steps,
+            report.naked_singles
+                + report.hidden_singles
+                + report.pointing_pairs
+                + report.box_line_reductions
+        );
+
+        // Empty grid, no technique can place a digit
+        let report = rate_difficulty(&to_grid([[0; 9]; 9]), GridShape::CLASSIC);
+        assert!(!report.solved);
+        assert_eq!(report.steps, 0);
+    }
+
+    #[test]
+    fn test_pointing_pair() {
+        // In the top-left box, 1 can only go in the top row, the other rows being filled
+        let mut grid = to_grid([[0; 9]; 9]);
+        grid[1][..3].copy_from_slice(&[2, 3, 4]);
+        grid[2][..3].copy_from_slice(&[5, 6, 7]);
+        let candidates = initial_candidates(&grid, GridShape::CLASSIC);
+
+        let step = pointing_pair(&grid, &candidates, GridShape::CLASSIC);
+        assert_eq!(
+            step,
+            Some(SolveStep::PointingPair {
+                digit: 1,
+                eliminations: (3..9).map(|c| (0, c)).collect()
+            })
+        );
+    }
+
+    #[test]
+    fn test_solve_singles() {
+        let mut grid = to_grid([
+            [0, 0, 3, 0, 2, 0, 6, 0, 0],
+            [9, 0, 0, 3, 0, 5, 0, 0, 1],
+            [0, 0, 1, 8, 0, 6, 4, 0, 0],
+            [0, 0, 8, 1, 0, 2, 9, 0, 0],
+            [7, 0, 0, 0, 0, 0, 0, 0, 8],
+            [0, 0, 6, 7, 0, 8, 2, 0, 0],
+            [0, 0, 2, 6, 0, 9, 5, 0, 0],
+            [8, 0, 0, 2, 0, 3, 0, 0, 9],
+            [0, 0, 5, 0, 1, 0, 3, 0, 0],
+        ]);
+        let solved = solve_singles(&grid, GridShape::CLASSIC).unwrap();
+        assert!(solved.iter().flatten().all(|&d| d != 0));
+
+        // A wrong digit leads to a broken grid
+        grid[0][0] = solved[0][1];
+        assert_eq!(solve_singles(&grid, GridShape::CLASSIC), None);
+        // Not enough to go on
+        assert_eq!(
+            solve_singles(&to_grid([[0; 9]; 9]), GridShape::CLASSIC),
+            None
+        );
+    }
+
+    #[test]
+    fn test_box_line_reduction() {
+        // In the top row, 1 can only go in the top-left box, the other cells being filled
+        let mut grid = to_grid([[0; 9]; 9]);
+        grid[0][3..].copy_from_slice(&[2, 3, 4, 5, 6, 7]);
+        let candidates = initial_candidates(&grid, GridShape::CLASSIC);
+
+        let step = box_line_reduction(&grid, &candidates, GridShape::CLASSIC);
+        let eliminations: Vec<(usize, usize)> =
+            (1..3).flat_map(|r| (0..3).map(move |c| (r, c))).collect();
+        assert_eq!(
+            step,
+            Some(SolveStep::BoxLineReduction {
+                digit: 1,
+                eliminations: eliminations.clone()
+            })
+        );
+
+        let mut candidates = candidates;
+        apply_step(
+            &mut grid,
+            &mut candidates,
+            GridShape::CLASSIC,
+            &step.unwrap(),
+        );
+        assert!(eliminations.iter().all(|&(r, c)| !candidates[r][c][0]));
+        assert!(candidates[0][0][0]);
+    }
+
+    #[test]
+    fn test_step_description() {
+        let step = SolveStep::NakedSingle {
+            row: 2,
+            col: 3,
+            digit: 5,
+        };
+        assert_eq!(
+            step.to_string(),
+            "Naked single: 5 is the only candidate left at row 3, column 4"
+        );
+        let step = SolveStep::PointingPair {
+            digit: 1,
+            eliminations: vec![(0, 3), (0, 4)],
+        };
+        assert!(step
+            .to_string()
+            .ends_with("remove it from row 1, column 4; row 1, column 5"));
+    }
+
+    #[test]
+    fn test_singles() {
+        // Every digit but 9 is in the first row, 9 is the only candidate of its last cell
+        let mut grid = to_grid([[0; 9]; 9]);
+        grid[0][..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let candidates = initial_candidates(&grid, GridShape::CLASSIC);
+        assert_eq!(
+            step(&grid, &candidates, GridShape::CLASSIC),
+            Some(SolveStep::NakedSingle {
+                row: 0,
+                col: 8,
+                digit: 9
+            })
+        );
+
+        // 1 is in rows 1 and 2 and columns 1 and 2, so the top-left box only has room for it
+        // in its corner, which still has other candidates
+        let mut grid = to_grid([[0; 9]; 9]);
+        grid[1][3] = 1;
+        grid[2][6] = 1;
+        grid[3][1] = 1;
+        grid[6][2] = 1;
+        let candidates = initial_candidates(&grid, GridShape::CLASSIC);
+        assert_eq!(naked_single(&grid, &candidates), None);
+        assert_eq!(
+            step(&grid, &candidates, GridShape::CLASSIC),
+            Some(SolveStep::HiddenSingle {
+                row: 0,
+                col: 0,
+                digit: 1
+            })
+        );
+    }
+}